@@ -0,0 +1,339 @@
+use cgmath::{InnerSpace, Matrix3, Matrix4, SquareMatrix, Vector3};
+
+use crate::mesh::Vertex;
+
+// Axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    // an empty box, which any point or box grows
+    pub fn empty() -> Self {
+        Self {
+            min: Vector3::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Vector3::new(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+
+    pub fn from_points(points: &[Vector3<f32>]) -> Self {
+        points.iter().fold(Self::empty(), |aabb, &point| aabb.grow(point))
+    }
+
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        vertices.iter().fold(Self::empty(), |aabb, vertex| aabb.grow(vertex.get_position().into()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&self, point: Vector3<f32>) -> Self {
+        Self {
+            min: Vector3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z)),
+            max: Vector3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z)),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        self.grow(other.min).grow(other.max)
+    }
+
+    pub fn get_center(&self) -> Vector3<f32> {
+        (self.min + self.max)/2.
+    }
+
+    pub fn get_half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min)/2.
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        let size = self.max - self.min;
+        size.x*size.y*size.z
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x
+            && self.min.y <= other.max.y && other.min.y <= self.max.y
+            && self.min.z <= other.max.z && other.min.z <= self.max.z
+    }
+
+    // 0 inside the box
+    pub fn get_distance(&self, point: Vector3<f32>) -> f32 {
+        let outside = Vector3::new(
+            (self.min.x - point.x).max(point.x - self.max.x).max(0.),
+            (self.min.y - point.y).max(point.y - self.max.y).max(0.),
+            (self.min.z - point.z).max(point.z - self.max.z).max(0.),
+        );
+        outside.magnitude()
+    }
+
+    // box of the transformed box (e.g. a mesh placed by its model matrix), which is larger than the box of the
+    // transformed points when there is a rotation but doesn't need to go through them
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        let center = (matrix*self.get_center().extend(1.)).truncate();
+        let half_extents = self.get_half_extents();
+        // each axis of the transformed box gets the projections of the half extents on it
+        let mut transformed_extents = Vector3::new(0., 0., 0.);
+        for i in 0..3 {
+            let column = matrix[i];
+            transformed_extents += Vector3::new(column.x.abs(), column.y.abs(), column.z.abs())*half_extents[i];
+        }
+        Self {
+            min: center - transformed_extents,
+            max: center + transformed_extents,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // Ritter's sphere (from the two farthest points found in two passes, grown to take in the points left outside)
+    // or the sphere around the center of the box, whichever is the smallest. The second one is exact for the
+    // symmetric primitives generated here while the first one is better for the lopsided meshes
+    pub fn from_points(points: &[Vector3<f32>]) -> Self {
+        if points.is_empty() {
+            return Self {
+                center: Vector3::new(0., 0., 0.),
+                radius: 0.,
+            };
+        }
+
+        let farthest = |from: Vector3<f32>| {
+            points.iter().copied().fold(from, |farthest, point| {
+                if (point - from).magnitude2() > (farthest - from).magnitude2() {point} else {farthest}
+            })
+        };
+        let a = farthest(points[0]);
+        let b = farthest(a);
+        let mut center = (a + b)/2.;
+        let mut radius = (b - a).magnitude()/2.;
+        for &point in points {
+            let distance = (point - center).magnitude();
+            if distance > radius {
+                // the new sphere goes through the point and the opposite side of the old one
+                let new_radius = (radius + distance)/2.;
+                center += (point - center)*((new_radius - radius)/distance);
+                radius = new_radius;
+            }
+        }
+
+        let box_center = Aabb::from_points(points).get_center();
+        let box_radius = points.iter().fold(0., |radius: f32, &point| radius.max((point - box_center).magnitude()));
+        if box_radius < radius {
+            Self {
+                center: box_center,
+                radius: box_radius,
+            }
+        } else {
+            Self {
+                center,
+                radius,
+            }
+        }
+    }
+
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        Self::from_points(&vertices.iter().map(|vertex| vertex.get_position().into()).collect::<Vec<_>>())
+    }
+}
+
+// Oriented bounding box, its axes are the columns of a rotation matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Obb {
+    pub center: Vector3<f32>,
+    pub axes: Matrix3<f32>,
+    pub half_extents: Vector3<f32>,
+}
+
+impl Obb {
+    // The axes are the principal axes of the points (eigenvectors of their covariance matrix), which fit elongated
+    // shapes well. The axis aligned box is kept when it is smaller as the principal axes of box-like shapes are
+    // about anything
+    pub fn from_points(points: &[Vector3<f32>]) -> Self {
+        let aabb = Aabb::from_points(points);
+        let axis_aligned = Self {
+            center: if aabb.is_empty() {Vector3::new(0., 0., 0.)} else {aabb.get_center()},
+            axes: Matrix3::identity(),
+            half_extents: if aabb.is_empty() {Vector3::new(0., 0., 0.)} else {aabb.get_half_extents()},
+        };
+        if points.len() < 2 {
+            return axis_aligned;
+        }
+
+        let mean = points.iter().fold(Vector3::new(0., 0., 0.), |sum, &point| sum + point)/(points.len() as f32);
+        let mut covariance = [[0.; 3]; 3];
+        for &point in points {
+            let d = point - mean;
+            for i in 0..3 {
+                for j in 0..3 {
+                    covariance[i][j] += d[i]*d[j];
+                }
+            }
+        }
+        let axes = get_eigenvectors(covariance);
+
+        // extents of the points projected on each axis
+        let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+        for &point in points {
+            for i in 0..3 {
+                let projection = axes[i].dot(point);
+                min[i] = min[i].min(projection);
+                max[i] = max[i].max(projection);
+            }
+        }
+        let local_center = (min + max)/2.;
+        let obb = Self {
+            center: axes*local_center,
+            axes,
+            half_extents: (max - min)/2.,
+        };
+        if obb.get_volume() < axis_aligned.get_volume() {obb} else {axis_aligned}
+    }
+
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        Self::from_points(&vertices.iter().map(|vertex| vertex.get_position().into()).collect::<Vec<_>>())
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        8.*self.half_extents.x*self.half_extents.y*self.half_extents.z
+    }
+
+    pub fn get_corners(&self) -> [Vector3<f32>; 8] {
+        let mut corners = [self.center; 8];
+        for (k, corner) in corners.iter_mut().enumerate() {
+            for i in 0..3 {
+                let sign = if k & (1 << i) == 0 {-1.} else {1.};
+                *corner += self.axes[i]*(sign*self.half_extents[i]);
+            }
+        }
+        corners
+    }
+}
+
+// Unit eigenvectors of a symmetric 3x3 matrix as the columns of a rotation matrix, by cyclic Jacobi rotations
+// (each one cancels an off-diagonal term, the matrix is diagonal after a few sweeps)
+pub(crate) fn get_eigenvectors(matrix: [[f32; 3]; 3]) -> Matrix3<f32> {
+    let mut a = matrix.map(|row| row.map(|value| value as f64));
+    let mut v = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+    for _ in 0..32 {
+        let off_diagonal = a[0][1]*a[0][1] + a[0][2]*a[0][2] + a[1][2]*a[1][2];
+        if off_diagonal < 1e-24 {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0. {
+                continue;
+            }
+            // angle of the rotation in the (p, q) plane cancelling a[p][q]
+            let theta = (a[q][q] - a[p][p])/(2.*a[p][q]);
+            let t = theta.signum()/(theta.abs() + (theta*theta + 1.).sqrt());
+            let c = 1./(t*t + 1.).sqrt();
+            let s = t*c;
+            for row in a.iter_mut() {
+                let (akp, akq) = (row[p], row[q]);
+                row[p] = c*akp - s*akq;
+                row[q] = s*akp + c*akq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            for k in 0..3 {
+                a[p][k] = c*row_p[k] - s*row_q[k];
+                a[q][k] = s*row_p[k] + c*row_q[k];
+            }
+            for row in v.iter_mut() {
+                let (vkp, vkq) = (row[p], row[q]);
+                row[p] = c*vkp - s*vkq;
+                row[q] = s*vkp + c*vkq;
+            }
+        }
+    }
+    // columns of v are the eigenvectors, made right handed
+    let column = |i: usize| Vector3::new(v[0][i] as f32, v[1][i] as f32, v[2][i] as f32);
+    let (x, y) = (column(0), column(1));
+    Matrix3::from_cols(x, y, x.cross(y))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::cylinder::Cylinder;
+    use crate::mesh::Mesh;
+
+    const NB_SLICES: usize = 32;
+    const RADIUS: f32 = 0.5;
+
+    // a cylinder of radius 0.5 between two points away from the origin, along no particular axis
+    fn rotated_cylinder() -> (Cylinder, Vector3<f32>, Vector3<f32>) {
+        let (start, end) = (Vector3::new(1., 2., -3.), Vector3::new(3., -1., -2.));
+        (Cylinder::between(NB_SLICES, start, end, RADIUS), start, end)
+    }
+
+    fn positions(mesh: &dyn Mesh) -> Vec<Vector3<f32>> {
+        mesh.get_vertices().iter().map(|vertex| vertex.get_position().into()).collect()
+    }
+
+    #[test]
+    fn aabb_of_a_rotated_cylinder() {
+        let (cylinder, start, end) = rotated_cylinder();
+        let aabb = cylinder.get_bounds();
+        // the rims stick out of the segment by r·sqrt(1 - d²) along each axis, d being the axis direction
+        let direction = (end - start).normalize();
+        // the slices miss the extreme points of the rims by at most this much
+        let tolerance = RADIUS*(1. - (PI/NB_SLICES as f32).cos()) + 1e-5;
+        for i in 0..3 {
+            let rim = RADIUS*(1. - direction[i]*direction[i]).sqrt();
+            let (min, max) = (start[i].min(end[i]) - rim, start[i].max(end[i]) + rim);
+            assert!(aabb.min[i] >= min - 1e-5 && aabb.min[i] <= min + tolerance, "axis {}: min {} for {}", i, aabb.min[i], min);
+            assert!(aabb.max[i] <= max + 1e-5 && aabb.max[i] >= max - tolerance, "axis {}: max {} for {}", i, aabb.max[i], max);
+        }
+    }
+
+    #[test]
+    fn sphere_of_a_rotated_cylinder() {
+        let (cylinder, start, end) = rotated_cylinder();
+        let sphere = BoundingSphere::from_vertices(cylinder.get_vertices());
+        // the smallest sphere goes through both rims
+        let smallest_radius = ((end - start).magnitude2()/4. + RADIUS*RADIUS).sqrt();
+        assert!(sphere.radius >= smallest_radius*(1. - 1e-3) && sphere.radius <= smallest_radius*1.05, "radius {}", sphere.radius);
+        for position in positions(&cylinder) {
+            assert!((position - sphere.center).magnitude() <= sphere.radius*(1. + 1e-5));
+        }
+    }
+
+    #[test]
+    fn obb_of_a_rotated_cylinder() {
+        let (cylinder, start, end) = rotated_cylinder();
+        let obb = Obb::from_vertices(cylinder.get_vertices());
+        let length = (end - start).magnitude();
+
+        // one axis follows the cylinder, the box is about the square prism around it
+        let direction = (end - start)/length;
+        let axis = (0..3).max_by(|&i, &j| obb.axes[i].dot(direction).abs().total_cmp(&obb.axes[j].dot(direction).abs())).unwrap();
+        assert!(obb.axes[axis].dot(direction).abs() > 0.999);
+        assert!((obb.half_extents[axis] - length/2.).abs() < 1e-3);
+        assert!((obb.center - (start + end)/2.).magnitude() < 1e-3);
+        assert!(obb.get_volume() <= 4.*RADIUS*RADIUS*length*1.001);
+        assert!(obb.get_volume() < cylinder.get_bounds().get_volume());
+
+        for position in positions(&cylinder) {
+            let local = position - obb.center;
+            for i in 0..3 {
+                assert!(obb.axes[i].dot(local).abs() <= obb.half_extents[i] + 1e-4);
+            }
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3};
+
+use crate::bounds::Aabb;
+use crate::mesh::Mesh;
+use crate::ray::{get_normal_matrix, Ray, RayHit};
+
+// a node holding this many primitives or less isn't split
+const MAX_LEAF_SIZE: usize = 4;
+
+// A leaf holds the primitives indices[first..first + count], an inner node (count = 0) has its two children
+// at nodes[first] & nodes[first + 1]
+struct BvhNode {
+    aabb: Aabb,
+    first: usize,
+    count: usize,
+}
+
+// Bounding volume hierarchy over any primitives given by their boxes (triangles, instances...). The queries
+// only deal with the boxes, the primitives themselves are tested by the closures given to them
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    // Each node is split in two halves at the median of the centers of its primitives along the axis where
+    // they spread the most, which keeps the tree balanced
+    pub fn new(aabbs: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            indices: (0..aabbs.len()).collect(),
+        };
+        if !aabbs.is_empty() {
+            let centers: Vec<Vector3<f32>> = aabbs.iter().map(|aabb| aabb.get_center()).collect();
+            bvh.nodes.push(BvhNode {
+                aabb: Aabb::empty(),
+                first: 0,
+                count: aabbs.len(),
+            });
+            bvh.split(0, aabbs, &centers);
+        }
+        bvh
+    }
+
+    fn split(&mut self, node: usize, aabbs: &[Aabb], centers: &[Vector3<f32>]) {
+        let (first, count) = (self.nodes[node].first, self.nodes[node].count);
+        let indices = &mut self.indices[first..first + count];
+        self.nodes[node].aabb = indices.iter().fold(Aabb::empty(), |aabb, &index| aabb.union(&aabbs[index]));
+        if count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let spread = indices.iter().fold(Aabb::empty(), |aabb, &index| aabb.grow(centers[index]));
+        let size = spread.max - spread.min;
+        let axis = if size.x >= size.y && size.x >= size.z {0} else if size.y >= size.z {1} else {2};
+        // all the centers at the same place can't be split
+        if size[axis] <= 0. {
+            return;
+        }
+        let middle = count/2;
+        indices.select_nth_unstable_by(middle, |&a, &b| centers[a][axis].total_cmp(&centers[b][axis]));
+
+        let left = self.nodes.len();
+        self.nodes.push(BvhNode {
+            aabb: Aabb::empty(),
+            first,
+            count: middle,
+        });
+        self.nodes.push(BvhNode {
+            aabb: Aabb::empty(),
+            first: first + middle,
+            count: count - middle,
+        });
+        self.nodes[node].first = left;
+        self.nodes[node].count = 0;
+        self.split(left, aabbs, centers);
+        self.split(left + 1, aabbs, centers);
+    }
+
+    pub fn get_bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.aabb)
+    }
+
+    // calls visit on the primitives of the leaves whose box and all of its parents' are accepted by enter
+    pub fn visit(&self, enter: impl FnMut(&Aabb) -> bool, mut visit: impl FnMut(usize)) {
+        self.visit_until(enter, |index| {
+            visit(index);
+            false
+        });
+    }
+
+    // same as visit but stops as soon as visit returns true, and tells whether it did
+    pub fn visit_until(&self, mut enter: impl FnMut(&Aabb) -> bool, mut visit: impl FnMut(usize) -> bool) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !enter(&node.aabb) {
+                continue;
+            }
+            if node.count > 0 {
+                for &index in &self.indices[node.first..node.first + node.count] {
+                    if visit(index) {
+                        return true;
+                    }
+                }
+            } else {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+            }
+        }
+        false
+    }
+
+    // Primitive with the smallest distance, e.g. along a ray or to a point. lower_bound gives a distance that
+    // nothing in the box can beat (None when nothing in it can be found) and distance the one of a primitive.
+    // The closest child is visited first & the nodes that can't beat the best primitive so far are skipped
+    pub fn find_closest(&self, lower_bound: impl Fn(&Aabb) -> Option<f32>, mut distance: impl FnMut(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        let root_bound = lower_bound(&self.nodes.first()?.aabb)?;
+        let mut closest: Option<(usize, f32)> = None;
+        let mut stack = vec![(0, root_bound)];
+        while let Some((node, bound)) = stack.pop() {
+            if closest.is_some_and(|(_, closest_distance)| bound >= closest_distance) {
+                continue;
+            }
+            let node = &self.nodes[node];
+            if node.count > 0 {
+                for &index in &self.indices[node.first..node.first + node.count] {
+                    if let Some(primitive_distance) = distance(index) {
+                        if closest.is_none_or(|(_, closest_distance)| primitive_distance < closest_distance) {
+                            closest = Some((index, primitive_distance));
+                        }
+                    }
+                }
+            } else {
+                let mut children = [node.first, node.first + 1].map(|child| (child, lower_bound(&self.nodes[child].aabb)));
+                // the far child is pushed first so that the stack pops the near one first
+                if children[0].1.unwrap_or(f32::INFINITY) < children[1].1.unwrap_or(f32::INFINITY) {
+                    children.swap(0, 1);
+                }
+                for (child, bound) in children {
+                    if let Some(bound) = bound {
+                        stack.push((child, bound));
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+// ray hit on a triangle of a mesh, given by its position in the index list
+#[derive(Clone, Copy, Debug)]
+pub struct TriangleHit {
+    pub triangle: usize,
+    pub hit: RayHit,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClosestPoint {
+    pub triangle: usize,
+    pub point: Vector3<f32>,
+    pub distance: f32,
+}
+
+// BVH over the triangles of a mesh, which keeps its own copy of the positions
+pub struct MeshBvh {
+    positions: Vec<Vector3<f32>>,
+    triangles: Vec<[u32; 3]>,
+    bvh: Bvh,
+}
+
+impl MeshBvh {
+    pub fn new(mesh: &dyn Mesh) -> Self {
+        let positions: Vec<Vector3<f32>> = mesh.get_vertices().iter().map(|vertex| vertex.get_position().into()).collect();
+        let triangles = mesh.get_indices().clone();
+        let aabbs: Vec<Aabb> = triangles.iter()
+            .map(|triangle| Aabb::from_points(&triangle.map(|index| positions[index as usize])))
+            .collect();
+        Self {
+            bvh: Bvh::new(&aabbs),
+            positions,
+            triangles,
+        }
+    }
+
+    pub fn get_bounds(&self) -> Aabb {
+        self.bvh.get_bounds()
+    }
+
+    // corners of the triangle, placed by the transform if any
+    fn get_triangle(&self, triangle: usize, transform: Option<&Matrix4<f32>>) -> [Vector3<f32>; 3] {
+        let corners = self.triangles[triangle].map(|index| self.positions[index as usize]);
+        match transform {
+            Some(transform) => corners.map(|corner| (transform*corner.extend(1.)).truncate()),
+            None => corners,
+        }
+    }
+
+    // closest hit from either side, the normal follows the winding of the triangle
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<TriangleHit> {
+        let (triangle, distance) = self.bvh.find_closest(
+            |aabb| ray.intersect_aabb(aabb),
+            |triangle| ray.intersect_triangle(self.get_triangle(triangle, None)),
+        )?;
+        let [a, b, c] = self.get_triangle(triangle, None);
+        Some(TriangleHit {
+            triangle,
+            hit: RayHit {
+                distance,
+                point: ray.get_point(distance),
+                normal: (b - a).cross(c - a).normalize(),
+            },
+        })
+    }
+
+    pub fn closest_point(&self, point: Vector3<f32>) -> Option<ClosestPoint> {
+        self.closest_point_transformed(point, None)
+    }
+
+    // triangles overlapping the box
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut triangles = Vec::new();
+        self.bvh.visit(
+            |node_aabb| node_aabb.overlaps(aabb),
+            |triangle| if triangle_overlaps_aabb(self.get_triangle(triangle, None), aabb) {triangles.push(triangle)},
+        );
+        triangles
+    }
+
+    // The closest point doesn't survive a non uniform scale so the triangles are placed by the transform instead
+    // of bringing the point into the space of the mesh. The boxes of the nodes are transformed the same way, which
+    // makes them larger but still bounds
+    fn closest_point_transformed(&self, point: Vector3<f32>, transform: Option<&Matrix4<f32>>) -> Option<ClosestPoint> {
+        let place = |aabb: &Aabb| transform.map_or(*aabb, |transform| aabb.transform(transform));
+        let (triangle, distance) = self.bvh.find_closest(
+            |aabb| Some(place(aabb).get_distance(point)),
+            |triangle| Some((get_closest_point_on_triangle(point, self.get_triangle(triangle, transform)) - point).magnitude()),
+        )?;
+        Some(ClosestPoint {
+            triangle,
+            point: get_closest_point_on_triangle(point, self.get_triangle(triangle, transform)),
+            distance,
+        })
+    }
+
+    // whether any triangle placed by the transform overlaps the box, stopping at the first one
+    fn overlaps_aabb_transformed(&self, aabb: &Aabb, transform: &Matrix4<f32>) -> bool {
+        self.bvh.visit_until(
+            |node_aabb| node_aabb.transform(transform).overlaps(aabb),
+            |triangle| triangle_overlaps_aabb(self.get_triangle(triangle, Some(transform)), aabb),
+        )
+    }
+}
+
+struct Instance {
+    mesh: usize,
+    transform: Matrix4<f32>,
+    inverse: Matrix4<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct InstanceHit {
+    pub instance: usize,
+    pub triangle: usize,
+    pub hit: RayHit,
+}
+
+// Two levels BVH: one over the placed instances of the scene, whose leaves go down into the BVH of their mesh
+pub struct SceneBvh {
+    meshes: Vec<MeshBvh>,
+    instances: Vec<Instance>,
+    bvh: Bvh,
+}
+
+impl SceneBvh {
+    // instances are given by the index of their mesh & their model matrix, which must be invertible
+    pub fn new(meshes: Vec<MeshBvh>, instances: &[(usize, Matrix4<f32>)]) -> Self {
+        let instances: Vec<Instance> = instances.iter().map(|&(mesh, transform)| Instance {
+            mesh,
+            transform,
+            inverse: transform.invert().expect("the model matrix of an instance isn't invertible"),
+        }).collect();
+        let aabbs: Vec<Aabb> = instances.iter()
+            .map(|instance| meshes[instance.mesh].get_bounds().transform(&instance.transform))
+            .collect();
+        Self {
+            bvh: Bvh::new(&aabbs),
+            meshes,
+            instances,
+        }
+    }
+
+    // closest hit, the ray is brought into the space of each instance it meets
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<InstanceHit> {
+        let intersect_instance = |instance: &Instance| self.meshes[instance.mesh].intersect_ray(&ray.transform(&instance.inverse));
+        let (instance_index, _) = self.bvh.find_closest(
+            |aabb| ray.intersect_aabb(aabb),
+            |instance| intersect_instance(&self.instances[instance]).map(|triangle_hit| triangle_hit.hit.distance),
+        )?;
+        let instance = &self.instances[instance_index];
+        let TriangleHit {triangle, hit} = intersect_instance(instance)?;
+        Some(InstanceHit {
+            instance: instance_index,
+            triangle,
+            hit: RayHit {
+                distance: hit.distance,
+                point: ray.get_point(hit.distance),
+                normal: (get_normal_matrix(&instance.inverse)*hit.normal).normalize(),
+            },
+        })
+    }
+
+    // closest point of the surface of any instance, with the index of that instance
+    pub fn closest_point(&self, point: Vector3<f32>) -> Option<(usize, ClosestPoint)> {
+        let closest_in_instance = |instance: &Instance| self.meshes[instance.mesh].closest_point_transformed(point, Some(&instance.transform));
+        let (instance, _) = self.bvh.find_closest(
+            |aabb| Some(aabb.get_distance(point)),
+            |instance| closest_in_instance(&self.instances[instance]).map(|closest| closest.distance),
+        )?;
+        Some((instance, closest_in_instance(&self.instances[instance])?))
+    }
+
+    // instances with a triangle overlapping the box, e.g. for selections or culling
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut instances = Vec::new();
+        self.bvh.visit(
+            |node_aabb| node_aabb.overlaps(aabb),
+            |instance_index| {
+                let instance = &self.instances[instance_index];
+                if self.meshes[instance.mesh].overlaps_aabb_transformed(aabb, &instance.transform) {
+                    instances.push(instance_index);
+                }
+            },
+        );
+        instances
+    }
+}
+
+// Closest point of the triangle abc to p, found from the Voronoi region of the triangle p is in
+// (Ericson, Real-Time Collision Detection 5.1.5)
+fn get_closest_point_on_triangle(p: Vector3<f32>, [a, b, c]: [Vector3<f32>; 3]) -> Vector3<f32> {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0. && d2 <= 0. {
+        return a;
+    }
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0. && d4 <= d3 {
+        return b;
+    }
+    let vc = d1*d4 - d3*d2;
+    if vc <= 0. && d1 >= 0. && d3 <= 0. {
+        return a + ab*(d1/(d1 - d3));
+    }
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0. && d5 <= d6 {
+        return c;
+    }
+    let vb = d5*d2 - d1*d6;
+    if vb <= 0. && d2 >= 0. && d6 <= 0. {
+        return a + ac*(d2/(d2 - d6));
+    }
+    let va = d3*d6 - d5*d4;
+    if va <= 0. && d4 - d3 >= 0. && d5 - d6 >= 0. {
+        return b + (c - b)*((d4 - d3)/((d4 - d3) + (d5 - d6)));
+    }
+    // inside the face
+    let denominator = 1./(va + vb + vc);
+    a + ab*(vb*denominator) + ac*(vc*denominator)
+}
+
+// Separating axis test between a triangle and a box (Akenine-Möller): the axes of the box, the normal of the
+// triangle & the cross products of their edges
+fn triangle_overlaps_aabb(triangle: [Vector3<f32>; 3], aabb: &Aabb) -> bool {
+    let center = aabb.get_center();
+    let half_extents = aabb.get_half_extents();
+    let corners = triangle.map(|corner| corner - center);
+    let separates = |axis: Vector3<f32>| {
+        let projections = corners.map(|corner| axis.dot(corner));
+        let min = projections[0].min(projections[1]).min(projections[2]);
+        let max = projections[0].max(projections[1]).max(projections[2]);
+        let radius = half_extents.x*axis.x.abs() + half_extents.y*axis.y.abs() + half_extents.z*axis.z.abs();
+        min > radius || max < -radius
+    };
+
+    let box_axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+    let edges = [corners[1] - corners[0], corners[2] - corners[1], corners[0] - corners[2]];
+    if box_axes.iter().any(|&axis| separates(axis)) || separates(edges[0].cross(edges[1])) {
+        return false;
+    }
+    // a cross product of parallel directions isn't an axis
+    !box_axes.iter().any(|&box_axis| edges.iter().any(|&edge| {
+        let axis = box_axis.cross(edge);
+        axis.magnitude2() > f32::EPSILON*edge.magnitude2() && separates(axis)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Matrix3};
+
+    use super::*;
+    use crate::cylinder::Cylinder;
+
+    // deterministic points in [-size, size]³
+    fn random_points(nb_points: usize, size: f32) -> Vec<Vector3<f32>> {
+        let mut state: u32 = 12345;
+        let mut random = move || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32/(1 << 24) as f32*2. - 1.
+        };
+        (0..nb_points).map(|_| Vector3::new(random(), random(), random())*size).collect()
+    }
+
+    // rays from points around the scene towards points inside it
+    fn random_rays(nb_rays: usize, size: f32) -> Vec<Ray> {
+        let origins = random_points(nb_rays, 2.*size);
+        let targets = random_points(2*nb_rays, size/2.);
+        origins.iter().zip(&targets[nb_rays..]).map(|(&origin, &target)| Ray::new(origin, (target - origin).normalize())).collect()
+    }
+
+    fn random_boxes(nb_boxes: usize, size: f32) -> Vec<Aabb> {
+        let centers = random_points(nb_boxes, size);
+        let extents = random_points(2*nb_boxes, size/4.);
+        centers.iter().zip(&extents[nb_boxes..]).map(|(&center, extent)| {
+            let extent = Vector3::new(extent.x.abs(), extent.y.abs(), extent.z.abs());
+            Aabb { min: center - extent, max: center + extent }
+        }).collect()
+    }
+
+    fn get_triangles(mesh: &dyn Mesh, transform: &Matrix4<f32>) -> Vec<[Vector3<f32>; 3]> {
+        mesh.get_indices().iter().map(|triangle| triangle.map(|index| {
+            (transform*Vector3::from(mesh.get_vertices()[index as usize].get_position()).extend(1.)).truncate()
+        })).collect()
+    }
+
+    fn min_distance(distances: impl Iterator<Item = f32>) -> Option<f32> {
+        distances.fold(None, |min: Option<f32>, distance| Some(min.map_or(distance, |min| min.min(distance))))
+    }
+
+    // a grid of 4 x 4 cylinders, each one rotated & scaled differently
+    fn grid() -> Vec<(usize, Matrix4<f32>)> {
+        (0..16).map(|i| {
+            let position = Vector3::new((i%4) as f32*3. - 4.5, 0., (i/4) as f32*3. - 4.5);
+            let rotation = Matrix4::from(Matrix3::from_angle_x(Deg(20.*i as f32)));
+            let scale = Matrix4::from_nonuniform_scale(1., 1. + 0.1*i as f32, 0.5 + 0.05*i as f32);
+            (i%2, Matrix4::from_translation(position)*rotation*scale)
+        }).collect()
+    }
+
+    #[test]
+    fn mesh_queries_match_brute_force() {
+        let cylinder = Cylinder::builder(24, 2., 1.).stacks(6).build();
+        let bvh = MeshBvh::new(&cylinder);
+        let triangles = get_triangles(&cylinder, &Matrix4::identity());
+
+        let mut nb_hits = 0;
+        for ray in random_rays(200, 4.) {
+            let brute_force = min_distance(triangles.iter().filter_map(|&triangle| ray.intersect_triangle(triangle)));
+            assert_eq!(bvh.intersect_ray(&ray).map(|hit| hit.hit.distance), brute_force);
+            nb_hits += brute_force.is_some() as usize;
+        }
+        // both hits & misses are compared
+        assert!(nb_hits > 20 && nb_hits < 180, "{} hits", nb_hits);
+        for point in random_points(200, 3.) {
+            let brute_force = min_distance(triangles.iter().map(|&triangle| (get_closest_point_on_triangle(point, triangle) - point).magnitude()));
+            let closest = bvh.closest_point(point).unwrap();
+            assert_eq!(Some(closest.distance), brute_force);
+            assert!(((closest.point - point).magnitude() - closest.distance).abs() < 1e-5);
+        }
+        for aabb in random_boxes(100, 2.) {
+            let brute_force: Vec<usize> = (0..triangles.len()).filter(|&i| triangle_overlaps_aabb(triangles[i], &aabb)).collect();
+            let mut found = bvh.query_aabb(&aabb);
+            found.sort();
+            assert_eq!(found, brute_force);
+        }
+    }
+
+    #[test]
+    fn scene_queries_match_brute_force() {
+        let meshes = [Cylinder::new(16, 2., 1.), Cylinder::builder(12, 1., 0.8).radii(0., 0.8).build()];
+        let instances = grid();
+        let scene = SceneBvh::new(meshes.iter().map(|mesh| MeshBvh::new(mesh)).collect(), &instances);
+        let placed: Vec<Vec<[Vector3<f32>; 3]>> = instances.iter().map(|(mesh, transform)| get_triangles(&meshes[*mesh], transform)).collect();
+
+        for ray in random_rays(200, 6.) {
+            let brute_force = min_distance(placed.iter().flatten().filter_map(|&triangle| ray.intersect_triangle(triangle)));
+            match (scene.intersect_ray(&ray), brute_force) {
+                (Some(hit), Some(distance)) => {
+                    assert!((hit.hit.distance - distance).abs() < 1e-4, "{} instead of {}", hit.hit.distance, distance);
+                    assert!((hit.hit.point - ray.get_point(distance)).magnitude() < 1e-3);
+                },
+                (None, None) => {},
+                (hit, distance) => panic!("{:?} instead of {:?}", hit.map(|hit| hit.hit.distance), distance),
+            }
+        }
+        for point in random_points(100, 8.) {
+            let brute_force = min_distance(placed.iter().flatten().map(|&triangle| (get_closest_point_on_triangle(point, triangle) - point).magnitude()));
+            let (_, closest) = scene.closest_point(point).unwrap();
+            assert!((closest.distance - brute_force.unwrap()).abs() < 1e-5);
+        }
+        let mut nb_found = 0;
+        for aabb in random_boxes(100, 6.) {
+            let brute_force: Vec<usize> = (0..placed.len())
+                .filter(|&instance| placed[instance].iter().any(|&triangle| triangle_overlaps_aabb(triangle, &aabb)))
+                .collect();
+            let mut found = scene.query_aabb(&aabb);
+            found.sort();
+            assert_eq!(found, brute_force);
+            nb_found += found.len();
+        }
+        assert!(nb_found > 0);
+    }
+}
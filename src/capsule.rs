@@ -0,0 +1,57 @@
+use std::f32::consts::PI;
+use std::ops::Deref;
+
+use crate::cylinder::{Cylinder, Ring};
+use crate::mesh::TriangleMesh;
+
+// Cylinder closed by two hemispheres instead of flat caps, the usual collision & character proxy shape
+pub struct Capsule(TriangleMesh);
+
+impl Deref for Capsule {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl Capsule {
+    // height is the length of the cylindrical part only, the capsule is height + 2*radius tall.
+    // Each hemisphere is made of nb_rings latitude bands
+    pub fn new(nb_slices: usize, nb_rings: usize, height: f32, radius: f32) -> Self {
+        assert!(nb_rings > 0, "a hemisphere needs at least one latitude band");
+
+        let mut vertices = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        // v follows the arc length of the profile, from the bottom pole to the top one
+        let total_length = height + PI*radius;
+        let ring = |latitude: f32, center_h: f32, arc_start: f32| Ring {
+            radius: radius*latitude.cos(),
+            h: center_h + radius*latitude.sin(),
+            normal: [latitude.cos(), latitude.sin()],
+            v: (arc_start + radius*(latitude + PI/2.))/total_length,
+        };
+
+        // top hemisphere from the pole to the equator, then bottom hemisphere from the equator to the pole.
+        // The band between both equators is the side wall of the cylinder, whose normals are horizontal
+        // like the ones of the hemispheres at the equator
+        let mut rings = Vec::new();
+        for i in 0..=nb_rings {
+            let latitude = PI/2. * (1. - (i as f32)/(nb_rings as f32));
+            rings.push(ring(latitude, height/2., height));
+        }
+        let first_bottom_ring = if height > 0. {0} else {1};
+        for i in first_bottom_ring..=nb_rings {
+            let latitude = -PI/2. * (i as f32)/(nb_rings as f32);
+            rings.push(ring(latitude, -height/2., 0.));
+        }
+        // poles collapse to a single vertex, cos(PI/2) isn't exactly 0 in floating point
+        rings.first_mut().unwrap().radius = 0.;
+        rings.last_mut().unwrap().radius = 0.;
+
+        Cylinder::push_rings(&mut vertices, &mut indices, &rings, nb_slices, 0., 2.*PI, false);
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+}
@@ -0,0 +1,38 @@
+use std::ops::Deref;
+
+use crate::mesh::TriangleMesh;
+use crate::plane::Plane;
+
+// Axis aligned box centered on the origin, each face has its own vertices and the whole texture
+pub struct Cuboid(TriangleMesh);
+
+impl Deref for Cuboid {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl Cuboid {
+    pub fn new(width: f32, height: f32, depth: f32) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        let (w, h, d) = (width/2., height/2., depth/2.);
+        // (center, u axis, v axis, size) of the +x, -x, +y, -y, +z & -z faces, v points up on the side faces
+        let faces = [
+            ([w, 0., 0.], [0., 0., -1.], [0., 1., 0.], [depth, height]),
+            ([-w, 0., 0.], [0., 0., 1.], [0., 1., 0.], [depth, height]),
+            ([0., h, 0.], [1., 0., 0.], [0., 0., -1.], [width, depth]),
+            ([0., -h, 0.], [1., 0., 0.], [0., 0., 1.], [width, depth]),
+            ([0., 0., d], [1., 0., 0.], [0., 1., 0.], [width, height]),
+            ([0., 0., -d], [-1., 0., 0.], [0., 1., 0.], [width, height]),
+        ];
+        for (center, u_axis, v_axis, size) in faces {
+            Plane::push_grid(&mut vertices, &mut indices, center, u_axis, v_axis, size, [1, 1]);
+        }
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+}
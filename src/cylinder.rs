@@ -1,115 +1,610 @@
-use gl::VertexP2ui;
+use std::f32::consts::PI;
+use std::ops::Deref;
 
-type vtx = [f32; 3];
+use cgmath::{InnerSpace, Matrix3, Matrix4, Rad, Vector3};
 
-#[derive(Debug)]
-pub struct Vertex {
-    vertex: vtx,
-    normal: vtx,
-}
-
-impl Vertex {
-    pub fn new(v: vtx, n: vtx) -> Self {
-        Self {
-            vertex:v,
-            normal:n,
-        }
-    }
-}
+use crate::mesh::{triangle, Mesh, TriangleMesh, Vertex};
 
-pub struct Cylinder {
-    vertices: Vec<Vertex>,
-    indices: Vec<[i32; 3]>,
+// One horizontal ring of a surface of revolution: its radius, its height, the (radial, vertical)
+// components of its normal and its v texture coordinate
+#[derive(Clone, Copy)]
+pub(crate) struct Ring {
+    pub radius: f32,
+    pub h: f32,
+    pub normal: [f32; 2],
+    pub v: f32,
 }
 
-impl Cylinder {
+pub struct Cylinder(TriangleMesh);
 
-    pub fn get_indices(&self) -> &Vec<[i32; 3]> {
-        &self.indices
-    }
+impl Deref for Cylinder {
+    type Target = TriangleMesh;
 
-    pub fn get_vertices(&self) -> &Vec<Vertex> {
-        &self.vertices
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
     }
+}
 
+impl Cylinder {
 
-    fn get_unit_circle_vertices(nb_slices: usize) -> (Vec<f32>, Vec<f32>) {
-        let PI = 3.14159265359;
-        let step_th = 2.*PI/ (nb_slices as f32);
+    // nb_slices + 1 points of the arc going from start_angle to start_angle + sweep_angle
+    pub(crate) fn get_unit_circle_vertices(nb_slices: usize, start_angle: f32, sweep_angle: f32) -> (Vec<f32>, Vec<f32>) {
+        let step_th = sweep_angle/ (nb_slices as f32);
         let mut current_angle;
 
         let mut cosines = Vec::new();
         let mut sines = Vec::new();
 
         for i in 0..=nb_slices {
-            current_angle = (i as f32) * step_th;
+            current_angle = start_angle + (i as f32) * step_th;
             cosines.push(current_angle.cos());
             sines.push(current_angle.sin());
         }
         (cosines, sines)
     }
 
+    // Surface of revolution around the y axis going through the given rings, from top to bottom.
+    // Each ring gets one extra vertex at the seam (same position as the first one but with u = 0
+    // instead of 1) so the texture doesn't wrap back across the last slice. When faceted, every
+    // slice gets its own two vertices instead. A ring of radius 0 collapses to a single apex vertex
+    // (one per slice when faceted) so no degenerate triangles are generated.
+    pub(crate) fn push_rings(vertices: &mut Vec<Vertex>, indices: &mut Vec<[u32; 3]>, rings: &[Ring], nb_slices: usize, start_angle: f32, sweep_angle: f32, faceted: bool) {
+        let (cosines, sines) = Self::get_unit_circle_vertices(nb_slices, start_angle, sweep_angle);
+        // a faceted slice uses the normal at the middle of its arc, i.e. the odd points of the arc split twice as much
+        let (mid_cosines, mid_sines) = Self::get_unit_circle_vertices(2*nb_slices, start_angle, sweep_angle);
+        let mut current_point;
+        let mut current_normal;
+        let mut current_uv;
+        let mut current_tangent;
+
+        // the left & right vertices of slice j in ring k are at ring_starts[k] + j*ring_strides[k] and ring_offsets[k] further
+        let mut ring_starts = Vec::new();
+        let mut ring_strides = Vec::new();
+        let mut ring_offsets = Vec::new();
+        for ring in rings {
+            let (radius, h, v) = (ring.radius, ring.h, ring.v);
+            let [normal_xz, normal_y] = ring.normal;
+
+            ring_starts.push(vertices.len());
+            if radius == 0. && !faceted {
+                ring_strides.push(0);
+                ring_offsets.push(0);
+                current_normal = [0., normal_y.signum(), 0.];
+                vertices.push(Vertex::new([0., h, 0.], current_normal, [0.5, v], [1., 0., 0., 1.]));
+            } else if radius == 0. {
+                ring_strides.push(1);
+                ring_offsets.push(0);
+                for j in 0..nb_slices {
+                    let (mid_cos, mid_sin) = (mid_cosines[2*j+1], mid_sines[2*j+1]);
+                    current_normal = [mid_cos*normal_xz, normal_y, mid_sin*normal_xz];
+                    current_uv = [1.0 - (j as f32 + 0.5)/(nb_slices as f32), v];
+                    vertices.push(Vertex::new([0., h, 0.], current_normal, current_uv, [mid_sin, 0., -mid_cos, 1.]));
+                }
+            } else if faceted {
+                ring_strides.push(2);
+                ring_offsets.push(1);
+                for j in 0..nb_slices {
+                    let (mid_cos, mid_sin) = (mid_cosines[2*j+1], mid_sines[2*j+1]);
+                    current_normal = [mid_cos*normal_xz, normal_y, mid_sin*normal_xz];
+                    // the slice is flat so its tangent is the one at the middle of the arc
+                    current_tangent = [mid_sin, 0., -mid_cos, 1.];
+                    for jj in [j, j+1] {
+                        current_point = [cosines[jj]*radius, h, sines[jj]*radius];
+                        current_uv = [1.0 - (jj as f32)/(nb_slices as f32), v];
+                        vertices.push(Vertex::new(current_point, current_normal, current_uv, current_tangent));
+                    }
+                }
+            } else {
+                ring_strides.push(1);
+                ring_offsets.push(1);
+                for j in 0..=nb_slices {
+                    current_point = [cosines[j]*radius, h, sines[j]*radius];
+                    current_normal = [cosines[j]*normal_xz, normal_y, sines[j]*normal_xz];
+                    // u goes around the circumference (decreasing with the angle so the texture isn't
+                    // mirrored when seen from outside), v goes from the bottom (0) to the top (1)
+                    current_uv = [1.0 - (j as f32)/(nb_slices as f32), v];
+                    // analytic tangent = derivative of the position along u
+                    current_tangent = [sines[j], 0., -cosines[j], 1.];
+                    vertices.push(Vertex::new(current_point, current_normal, current_uv, current_tangent));
+                }
+            }
+        }
+
+        let ring_index = |k: usize, j: usize| {
+            let left = ring_starts[k] + j*ring_strides[k];
+            (left, left + ring_offsets[k])
+        };
+        for k in 0..rings.len()-1 {
+            for j in 0..nb_slices {
+                // quad between rings k & k+1 of columns j & j+1, only one triangle is left next to an apex
+                let (a, c) = ring_index(k, j);
+                let (b, d) = ring_index(k+1, j);
+                if ring_offsets[k] != 0 {
+                    indices.push(triangle(a, c, b));
+                }
+                if ring_offsets[k+1] != 0 {
+                    indices.push(triangle(c, d, b));
+                }
+            }
+        }
+    }
+
+    pub fn new(nb_slices: usize, height:f32, radius: f32) -> Self {
+        CylinderBuilder::new(nb_slices, height, radius).build()
+    }
+
+    pub fn builder(nb_slices: usize, height:f32, radius: f32) -> CylinderBuilder {
+        CylinderBuilder::new(nb_slices, height, radius)
+    }
+
+    // cylinder going from start to end, baked in world space (for bonds, struts & graph edges)
+    pub fn between(nb_slices: usize, start: Vector3<f32>, end: Vector3<f32>, radius: f32) -> Self {
+        let axis = end - start;
+        let cylinder = Self::new(nb_slices, axis.magnitude(), radius);
+        let rotation = Self::get_alignment_rotation(axis);
+        let center = (start + end)/2.;
+        let vertices = cylinder.get_vertices().iter().map(|vertex| {
+            let [x, y, z, w] = vertex.get_tangent();
+            let position = rotation*Vector3::from(vertex.get_position()) + center;
+            let normal = rotation*Vector3::from(vertex.get_normal());
+            let tangent = rotation*Vector3::new(x, y, z);
+            Vertex::new(position.into(), normal.into(), vertex.get_uv(), [tangent.x, tangent.y, tangent.z, w])
+        }).collect();
+        Self(TriangleMesh::new(vertices, cylinder.get_indices().clone()))
+    }
+
+    // Model matrix placing a cylinder of height 1 & radius 1 (e.g. Cylinder::new(nb_slices, 1., 1.)) between
+    // start and end with the given radius, so a single mesh can be instanced for all the segments
+    pub fn get_segment_transform(start: Vector3<f32>, end: Vector3<f32>, radius: f32) -> Matrix4<f32> {
+        let axis = end - start;
+        Matrix4::from_translation((start + end)/2.)
+            * Matrix4::from(Self::get_alignment_rotation(axis))
+            * Matrix4::from_nonuniform_scale(radius, axis.magnitude(), radius)
+    }
+
+    // rotation bringing the y axis of the cylinder onto the given direction
+    fn get_alignment_rotation(direction: Vector3<f32>) -> Matrix3<f32> {
+        let length = direction.magnitude();
+        // a zero length segment keeps the cylinder upright
+        if length < f32::EPSILON {
+            return Matrix3::from_scale(1.);
+        }
+        let direction = direction/length;
+        let axis = Vector3::unit_y().cross(direction);
+        // the rotation axis is undefined when the direction is (anti-)parallel to y: nothing to do when it
+        // goes up, a half turn around x (which keeps the cylinder symmetric) when it goes down
+        if axis.magnitude() < 1e-6 {
+            if direction.y > 0. {
+                Matrix3::from_scale(1.)
+            } else {
+                Matrix3::from_angle_x(Rad(PI))
+            }
+        } else {
+            Matrix3::from_axis_angle(axis.normalize(), Rad(direction.y.clamp(-1., 1.).acos()))
+        }
+    }
+}
+
+// How the side wall normals are generated
+#[derive(Clone, Copy, Debug)]
+pub enum Shading {
+    // normals follow the ideal round surface
+    Smooth,
+    // one normal per slice with split vertices, for prisms
+    Flat,
+    // flat when the angle between two neighboring slices is above the crease angle, smooth otherwise
+    AutoSmooth(Rad<f32>),
+}
+
+// Optional settings for the cylinder generation, `Cylinder::new` uses the defaults
+pub struct CylinderBuilder {
+    nb_slices: usize,
+    nb_stacks: usize,
+    height: f32,
+    top_radius: f32,
+    bottom_radius: f32,
+    start_angle: f32,
+    sweep_angle: f32,
+    top_cap: bool,
+    bottom_cap: bool,
+    shading: Shading,
+    bevel_radius: f32,
+    nb_bevel_segments: usize,
+}
+
+impl CylinderBuilder {
     pub fn new(nb_slices: usize, height:f32, radius: f32) -> Self {
+        Self {
+            nb_slices,
+            nb_stacks: 1,
+            height,
+            top_radius: radius,
+            bottom_radius: radius,
+            start_angle: 0.,
+            sweep_angle: 2.*PI,
+            top_cap: true,
+            bottom_cap: true,
+            shading: Shading::Smooth,
+            bevel_radius: 0.,
+            nb_bevel_segments: 1,
+        }
+    }
+
+    // number of subdivisions of the side wall along the height (nb_stacks + 1 rings)
+    pub fn stacks(mut self, nb_stacks: usize) -> Self {
+        self.nb_stacks = nb_stacks.max(1);
+        self
+    }
+
+    // different radii give a frustum, a zero radius at one end gives a cone
+    pub fn radii(mut self, top_radius: f32, bottom_radius: f32) -> Self {
+        assert!(top_radius >= 0. && bottom_radius >= 0., "cylinder radii can't be negative");
+        assert!(top_radius > 0. || bottom_radius > 0., "at least one of the cylinder radii must be non zero");
+        self.top_radius = top_radius;
+        self.bottom_radius = bottom_radius;
+        self
+    }
+
+    // only generates the arc going from start_angle to start_angle + sweep_angle, the cut is closed by two flat faces
+    pub fn sweep<A: Into<Rad<f32>>>(mut self, start_angle: A, sweep_angle: A) -> Self {
+        let Rad(sweep_angle) = sweep_angle.into();
+        assert!(sweep_angle > 0., "the sweep angle must be positive");
+        self.start_angle = start_angle.into().0;
+        self.sweep_angle = sweep_angle.min(2.*PI);
+        self
+    }
+
+    // leaving out a cap gives an open-ended cylinder
+    pub fn caps(mut self, top_cap: bool, bottom_cap: bool) -> Self {
+        self.top_cap = top_cap;
+        self.bottom_cap = bottom_cap;
+        self
+    }
+
+    pub fn shading(mut self, shading: Shading) -> Self {
+        self.shading = shading;
+        self
+    }
+
+    // shortcut for Shading::AutoSmooth
+    pub fn crease_angle<A: Into<Rad<f32>>>(self, crease_angle: A) -> Self {
+        self.shading(Shading::AutoSmooth(crease_angle.into()))
+    }
+
+    // rounds the rims between the side wall and the caps with a fillet of nb_segments bands, a single band
+    // gives a flat chamfer. The radius is clamped so that the fillets fit on the caps and the side wall
+    pub fn bevel(mut self, bevel_radius: f32, nb_segments: usize) -> Self {
+        assert!(bevel_radius >= 0., "the bevel radius can't be negative");
+        self.bevel_radius = bevel_radius;
+        self.nb_bevel_segments = nb_segments.max(1);
+        self
+    }
+
+    fn is_full_turn(&self) -> bool {
+        self.sweep_angle >= 2.*PI
+    }
+
+    // whether the edges between the slices of the side wall are hard. The rims between
+    // the side wall and the caps are always hard
+    fn is_faceted(&self, normal_xz: f32, normal_y: f32) -> bool {
+        match self.shading {
+            Shading::Smooth => false,
+            Shading::Flat => true,
+            Shading::AutoSmooth(Rad(crease_angle)) => {
+                // angle between the normals of two neighboring slices, which differ by one step around the axis
+                let step = self.sweep_angle/(self.nb_slices as f32);
+                let cos_angle = normal_xz*normal_xz*step.cos() + normal_y*normal_y;
+                cos_angle.clamp(-1., 1.).acos() > crease_angle
+            }
+        }
+    }
+
+    pub fn build(&self) -> Cylinder {
+        let (nb_slices, height) = (self.nb_slices, self.height);
 
         let mut vertices = Vec::new();
-        let mut indices:Vec<[i32;3]> = Vec::new();
+        let mut indices:Vec<[u32; 3]> = Vec::new();
         
-        let (cosines, sines) = Self::get_unit_circle_vertices(nb_slices);
-        let mut current_point;
-        let mut current_normal;
-        let mut index;
-        let mut h;
+        let (cosines, sines) = Cylinder::get_unit_circle_vertices(nb_slices, self.start_angle, self.sweep_angle);
 
-        for j in 0..nb_slices {
+        // the side normal is tilted by the slope of the wall: (cos*height, bottom_radius - top_radius, sin*height)
+        let slope = self.bottom_radius - self.top_radius;
+        let normal_length = (height*height + slope*slope).sqrt();
+        // a flat cylinder without slope has no side wall to tilt the normal, it keeps the radial one
+        let (normal_xz, normal_y) = if normal_length > 0. {(height/normal_length, slope/normal_length)} else {(1., 0.)};
+
+        let profile = self.get_side_profile(normal_xz, normal_y);
+        let faceted = self.is_faceted(normal_xz, normal_y);
+        for rings in &profile {
+            Cylinder::push_rings(&mut vertices, &mut indices, rings, nb_slices, self.start_angle, self.sweep_angle, faceted);
+        }
 
-            index = 2*j;
-            indices.push([index.try_into().unwrap(), (index+1).try_into().unwrap(), ((index+2)%(2*nb_slices)).try_into().unwrap()]);
-            indices.push([((index+2)%(2*nb_slices)).try_into().unwrap(), (index+1).try_into().unwrap(), ((index+3)%(2*nb_slices)).try_into().unwrap()] as [i32;3]);
+        // a bevel shrinks the caps down to where the fillets start
+        let top_radius = profile[0][0].radius;
+        let bottom_radius = profile[profile.len()-1][profile[profile.len()-1].len()-1].radius;
+        let full_turn = self.is_full_turn();
+        if self.top_cap && top_radius > 0. {
+            Self::push_cap(&mut vertices, &mut indices, (&cosines, &sines), height/2., top_radius, true, full_turn);
+        }
+        if self.bottom_cap && bottom_radius > 0. {
+            Self::push_cap(&mut vertices, &mut indices, (&cosines, &sines), -height/2., bottom_radius, false, full_turn);
+        }
+
+        if !full_turn {
+            // the cut faces follow the whole profile, without the rings repeated between two pieces
+            let mut rings: Vec<Ring> = profile.into_iter().flatten().collect();
+            rings.dedup_by(|ring, previous| ring.radius == previous.radius && ring.h == previous.h);
+            self.push_cut_face(&mut vertices, &mut indices, &rings, cosines[0], sines[0], true);
+            self.push_cut_face(&mut vertices, &mut indices, &rings, cosines[nb_slices], sines[nb_slices], false);
+        }
+
+        Cylinder(TriangleMesh::new(vertices, indices))
+
+    }
+
+    // Rings of the side wall from top to bottom, split into pieces joined by hard edges: the top fillet,
+    // the straight part (nb_stacks + 1 rings) and the bottom fillet. Only the rims closed by a cap are
+    // beveled. A fillet is an arc of circle tangent to both the cap & the side wall, going from the cap
+    // normal to the side normal, and v follows the length of the whole profile
+    fn get_side_profile(&self, normal_xz: f32, normal_y: f32) -> Vec<Vec<Ring>> {
+        let half_height = self.height/2.;
+        // angle of the side normal from the +y axis
+        let side_angle = normal_xz.atan2(normal_y);
+        // distance from a rim to both ends of its fillet, per unit of bevel radius
+        // a flat side wall (a zero-height frustum) can't be tangent to the caps, it isn't beveled
+        let flat_side = normal_xz < 1e-6;
+        let (top_tan, bottom_tan) = if flat_side {(0., 0.)} else {((1. - normal_y)/normal_xz, (1. + normal_y)/normal_xz)};
+
+        let top_bevel = !flat_side && self.top_cap && self.top_radius > 0.;
+        let bottom_bevel = !flat_side && self.bottom_cap && self.bottom_radius > 0.;
+        let mut bevel_radius = self.bevel_radius;
+        let mut side_tan = 0.;
+        if top_bevel {
+            bevel_radius = bevel_radius.min(self.top_radius/top_tan);
+            side_tan += top_tan;
+        }
+        if bottom_bevel {
+            bevel_radius = bevel_radius.min(self.bottom_radius/bottom_tan);
+            side_tan += bottom_tan;
+        }
+        let side_length = self.height.hypot(self.bottom_radius - self.top_radius);
+        if side_tan > 0. {
+            bevel_radius = bevel_radius.min(side_length/side_tan);
+        }
+        let top_bevel_radius = if top_bevel {bevel_radius} else {0.};
+        let bottom_bevel_radius = if bottom_bevel {bevel_radius} else {0.};
+
+        // centers of the fillets in the (radial, vertical) plane, the rims themselves without bevel
+        let top_center = [self.top_radius - top_bevel_radius*top_tan, half_height - top_bevel_radius];
+        let bottom_center = [self.bottom_radius - bottom_bevel_radius*bottom_tan, -half_height + bottom_bevel_radius];
+        let ring = |center: [f32; 2], radius: f32, angle: f32, normal: [f32; 2]| {
+            let ring_radius = center[0] + radius*angle.sin();
+            Ring {
+                // a fillet eating the whole cap ends on the axis
+                radius: if ring_radius < 1e-6*self.top_radius.max(self.bottom_radius) {0.} else {ring_radius},
+                h: center[1] + radius*angle.cos(),
+                normal,
+                v: 0.,
+            }
+        };
+        let fillet = |center: [f32; 2], radius: f32, start_angle: f32, end_angle: f32| -> Vec<Ring> {
+            let nb_segments = self.nb_bevel_segments;
+            if nb_segments == 1 {
+                // a chamfer is flat so both its rings use the normal of the middle of the arc
+                let mid_angle = (start_angle + end_angle)/2.;
+                [start_angle, end_angle].iter()
+                    .map(|&angle| ring(center, radius, angle, [mid_angle.sin(), mid_angle.cos()]))
+                    .collect()
+            } else {
+                (0..=nb_segments).map(|i| {
+                    let angle = start_angle + (end_angle - start_angle)*(i as f32)/(nb_segments as f32);
+                    ring(center, radius, angle, [angle.sin(), angle.cos()])
+                }).collect()
+            }
+        };
+
+        let mut profile = Vec::new();
+        if top_bevel_radius > 0. {
+            profile.push(fillet(top_center, top_bevel_radius, 0., side_angle));
+        }
+        let top = ring(top_center, top_bevel_radius, side_angle, [normal_xz, normal_y]);
+        let bottom = ring(bottom_center, bottom_bevel_radius, side_angle, [normal_xz, normal_y]);
+        // the fillets can eat the whole side wall
+        if profile.is_empty() || (top.h - bottom.h).hypot(top.radius - bottom.radius) > 1e-6*side_length {
+            profile.push((0..=self.nb_stacks).map(|k| {
+                let t = (k as f32)/(self.nb_stacks as f32);
+                Ring {
+                    radius: top.radius + (bottom.radius - top.radius)*t,
+                    h: top.h + (bottom.h - top.h)*t,
+                    ..top
+                }
+            }).collect());
+        }
+        if bottom_bevel_radius > 0. {
+            profile.push(fillet(bottom_center, bottom_bevel_radius, side_angle, PI));
+        }
 
-            for i in 0..2 {
-                // adding current vertex to the vect of vertices
-                h = height/2.0 - (i as f32)*height;
-                current_point = [cosines[j]*radius, h, sines[j]*radius];
-                current_normal = [cosines[j], 0., sines[j]];
-                vertices.push(Vertex::new(current_point, current_normal));
+        // v is 1 at the top & 0 at the bottom, proportionally to the distance along the profile
+        let mut lengths = Vec::new();
+        let mut length = 0.;
+        let mut previous: Option<[f32; 2]> = None;
+        for ring in profile.iter().flatten() {
+            if let Some([radius, h]) = previous {
+                length += (ring.h - h).hypot(ring.radius - radius);
             }
+            lengths.push(length);
+            previous = Some([ring.radius, ring.h]);
+        }
+        // all the rings of a flat cylinder are at the same place, they are spread evenly instead
+        let last_ring = (lengths.len() - 1) as f32;
+        for (i, (ring, ring_length)) in profile.iter_mut().flatten().zip(lengths).enumerate() {
+            ring.v = if length > 0. {1. - ring_length/length} else {1. - (i as f32)/last_ring};
         }
+        profile
+    }
 
-        let nb_side_vertices = vertices.len();
+    // flat disc closing one end of the cylinder, around the unit circle vertices (cosines, sines): a center vertex
+    // followed by a ring of nb_slices vertices
+    // for a full turn (the last slice wraps around to the first ring vertex), nb_slices + 1 otherwise
+    pub(crate) fn push_cap(vertices: &mut Vec<Vertex>, indices: &mut Vec<[u32; 3]>, circle: (&[f32], &[f32]), h: f32, radius: f32, top: bool, full_turn: bool) {
+        let (cosines, sines) = circle;
+        let nb_slices = cosines.len() - 1;
+        let nb_ring_vertices = if full_turn {nb_slices} else {nb_slices + 1};
+        let sign = if top {1.0} else {-1.0};
+        let center_index = vertices.len();
 
-        // adding top & bottom center vertices and update of indices
-        let top_index = nb_side_vertices + nb_slices*2;
-        let bottom_index = top_index + 1;
+        // u follows +x on both caps, v follows -z on the top and +z on the bottom
+        vertices.push(Vertex::new([0., h, 0.], [0., sign, 0.], [0.5, 0.5], [1., 0., 0., 1.]));
+        for j in 0..nb_ring_vertices {
+            let current_point = [cosines[j]*radius, h, sines[j]*radius];
+            // planar disc mapping, flipped on the bottom cap so it reads the right way from below
+            let current_uv = [0.5 + 0.5*cosines[j], 0.5 - sign*0.5*sines[j]];
+            vertices.push(Vertex::new(current_point, [0., sign, 0.], current_uv, [1., 0., 0., 1.]));
+        }
 
         for j in 0..nb_slices {
+            let index = center_index + 1 + j;
+            let next_index = center_index + 1 + (j+1)%nb_ring_vertices;
+            if top {
+                indices.push(triangle(next_index, index, center_index));
+            } else {
+                indices.push(triangle(index, next_index, center_index));
+            }
+        }
+    }
+
+    // flat face closing a partial sweep at angle (cos, sin), from the axis to the side wall. It goes
+    // through the rings of the side wall so both share their edges. u goes from the axis to the rim on the
+    // end face and the other way on the start face so that both read the right way from outside, v goes
+    // from the bottom to the top
+    fn push_cut_face(&self, vertices: &mut Vec<Vertex>, indices: &mut Vec<[u32; 3]>, rings: &[Ring], cos: f32, sin: f32, start: bool) {
+        let max_radius = rings.iter().fold(0., |max_radius: f32, ring| max_radius.max(ring.radius));
+        let (normal, tangent) = if start {
+            ([sin, 0., -cos], [-cos, 0., -sin, 1.])
+        } else {
+            ([-sin, 0., cos], [cos, 0., sin, 1.])
+        };
+        let u = |radius: f32| if start {1. - radius/max_radius} else {radius/max_radius};
 
-            if j < nb_slices {
-                // handling indices
-            index = j*2 + nb_side_vertices;
-            indices.push([(index+2).try_into().unwrap(), index.try_into().unwrap(), top_index.try_into().unwrap()]);
-            indices.push([(index+3).try_into().unwrap(), (index+1).try_into().unwrap(), bottom_index.try_into().unwrap()]);
+        // an axis vertex & a rim vertex per ring of the side wall, from top to bottom
+        let first_index = vertices.len();
+        for ring in rings {
+            let (radius, h, v) = (ring.radius, ring.h, ring.v);
+            vertices.push(Vertex::new([0., h, 0.], normal, [u(0.), v], tangent));
+            vertices.push(Vertex::new([cos*radius, h, sin*radius], normal, [u(radius), v], tangent));
+        }
+
+        for k in 0..rings.len()-1 {
+            let (axis, rim) = (first_index + 2*k, first_index + 2*k + 1);
+            let (axis_below, rim_below) = (axis + 2, rim + 2);
+            // a cone apex turns the rectangle into a triangle
+            if rings[k].radius > 0. {
+                if start {
+                    indices.push(triangle(axis, rim, rim_below));
+                } else {
+                    indices.push(triangle(axis, rim_below, rim));
+                }
             }
-            
-            for i in 0..2 {
-                h = height/2.0 - (i as f32)*height;
-                current_point = [cosines[j]*radius, h, sines[j]*radius];
-                current_normal = [0., 1.0 - 2.0 * (i as f32), 0.];
-                vertices.push(Vertex::new(current_point, current_normal));
+            if rings[k+1].radius > 0. {
+                if start {
+                    indices.push(triangle(axis, rim_below, axis_below));
+                } else {
+                    indices.push(triangle(axis, axis_below, rim_below));
+                }
             }
         }
+    }
+}
 
-        // last triangles which need to be treated separately
-        let last_index = 2*(nb_slices-1)+nb_side_vertices;
-        indices.push([(nb_side_vertices).try_into().unwrap(), (last_index).try_into().unwrap(), top_index.try_into().unwrap()]);
-        indices.push([(nb_side_vertices+1).try_into().unwrap(), (last_index+1).try_into().unwrap(), bottom_index.try_into().unwrap()]);
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
 
-        vertices.push(Vertex::new([0., height/2., 0.], [0., 1., 0.]));
-        vertices.push(Vertex::new([0., -height/2., 0.], [0., -1., 0.]));
+    use super::*;
+    use crate::mass::mesh_mass_properties;
+    use crate::validate::validate_mesh;
 
-        Self {
-            vertices: vertices,
-            indices: indices,
+    fn check_tangent_frames(cylinder: &Cylinder) {
+        for vertex in cylinder.get_vertices() {
+            let normal = Vector3::from(vertex.get_normal());
+            let [tx, ty, tz, w] = vertex.get_tangent();
+            let tangent = Vector3::new(tx, ty, tz);
+            assert!((normal.magnitude() - 1.).abs() < 1e-5, "|n| = {}", normal.magnitude());
+            assert!((tangent.magnitude() - 1.).abs() < 1e-5, "|t| = {}", tangent.magnitude());
+            assert!(normal.dot(tangent).abs() < 1e-5, "n.t = {}", normal.dot(tangent));
+            assert!(w == 1. || w == -1., "w = {}", w);
         }
+    }
 
+    fn is_finite(cylinder: &Cylinder) -> bool {
+        cylinder.get_vertices().iter().all(|vertex| {
+            let [u, v] = vertex.get_uv();
+            vertex.get_position().iter().chain(&vertex.get_normal()).chain(&vertex.get_tangent()).chain(&[u, v]).all(|x| x.is_finite())
+        })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn flat_cylinders_are_finite() {
+        assert!(is_finite(&Cylinder::new(8, 0., 0.5)));
+        assert!(is_finite(&Cylinder::builder(8, 0., 0.5).stacks(3).sweep(Deg(0.), Deg(90.)).build()));
+        // the side wall of a flat frustum is an annulus facing up
+        let annulus = Cylinder::builder(8, 0., 0.5).radii(0.3, 0.5).build();
+        assert!(is_finite(&annulus));
+        assert!(annulus.get_vertices().iter().all(|vertex| vertex.get_position()[1] == 0.));
+    }
+
+    #[test]
+    fn flat_cylinders_are_not_beveled() {
+        let beveled = Cylinder::builder(8, 0., 0.5).bevel(0.1, 3).build();
+        assert!(is_finite(&beveled));
+        assert_eq!(beveled.get_vertices().len(), Cylinder::new(8, 0., 0.5).get_vertices().len());
+        assert!(is_finite(&Cylinder::builder(8, 0., 0.5).radii(0.3, 0.5).bevel(0.1, 3).build()));
+    }
+
+    #[test]
+    fn bevels_are_closed_and_smooth() {
+        let bevels = [
+            Cylinder::builder(32, 1., 0.5).bevel(0.1, 4).build(),
+            Cylinder::builder(32, 1., 0.5).radii(0.3, 0.5).bevel(0.1, 4).build(),
+            Cylinder::builder(32, 1., 0.5).stacks(3).bevel(0.1, 1).build(),
+            // clamped so the fillets meet in the middle of the side wall
+            Cylinder::builder(32, 0.2, 0.5).bevel(0.3, 4).build(),
+        ];
+        let volume = mesh_mass_properties(&Cylinder::new(32, 1., 0.5)).volume;
+        for (i, beveled) in bevels.iter().enumerate() {
+            let report = validate_mesh(beveled);
+            assert!(report.is_watertight(), "bevel {}: {}", i, report);
+            assert!(is_finite(beveled));
+            if i < 3 {
+                assert!(mesh_mass_properties(beveled).volume < volume, "bevel {}", i);
+            }
+        }
+
+        // the fillets are tangent to the caps & the side wall, so the vertices split along their edges keep
+        // the same normal (a chamfer is flat so it isn't)
+        for beveled in &bevels[..2] {
+            for vertex in beveled.get_vertices() {
+                for other in beveled.get_vertices() {
+                    if (Vector3::from(other.get_position()) - Vector3::from(vertex.get_position())).magnitude() < 1e-6 {
+                        let difference = Vector3::from(other.get_normal()) - Vector3::from(vertex.get_normal());
+                        assert!(difference.magnitude() < 1e-5, "{:?} and {:?}", vertex.get_normal(), other.get_normal());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tangent_frames_are_orthonormal() {
+        for nb_slices in [3, 4, 10, 32] {
+            check_tangent_frames(&Cylinder::new(nb_slices, 1., 0.5));
+            check_tangent_frames(&Cylinder::builder(nb_slices, 2., 0.5).stacks(5).build());
+            check_tangent_frames(&Cylinder::builder(nb_slices, 1., 0.5).radii(0.2, 0.7).build());
+            check_tangent_frames(&Cylinder::builder(nb_slices, 1., 0.5).radii(0., 0.5).build());
+            check_tangent_frames(&Cylinder::builder(nb_slices, 1., 0.5).sweep(Deg(45.), Deg(180.)).build());
+            check_tangent_frames(&Cylinder::builder(nb_slices, 1., 0.5).shading(Shading::Flat).build());
+            check_tangent_frames(&Cylinder::builder(nb_slices, 1., 0.5).radii(0.3, 0.6).stacks(2)
+                .sweep(Deg(-30.), Deg(300.)).shading(Shading::Flat).build());
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::f32::consts::PI;
+use std::ops::Deref;
+
+use crate::cylinder::{Cylinder, CylinderBuilder};
+use crate::mesh::TriangleMesh;
+
+// Flat disc in the xz plane facing +y, same fan & planar uv mapping as the cylinder caps
+pub struct Disc(TriangleMesh);
+
+impl Deref for Disc {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl Disc {
+    pub fn new(nb_slices: usize, radius: f32) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        let (cosines, sines) = Cylinder::get_unit_circle_vertices(nb_slices, 0., 2.*PI);
+        CylinderBuilder::push_cap(&mut vertices, &mut indices, (&cosines, &sines), 0., radius, true, true);
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+}
@@ -0,0 +1,281 @@
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+
+use cgmath::{Matrix, Matrix3, Quaternion, Rad, Vector3};
+
+use crate::camera::Camera;
+use crate::mesh::{IndexBuffer, Mesh};
+
+// Snapshot of what the viewer draws: one mesh instanced at several positions, seen from the camera & lit by a point light
+pub struct GltfScene<'a> {
+    pub mesh: &'a dyn Mesh,
+    pub positions: &'a [Vector3<f32>],
+    pub camera: &'a Camera,
+    pub aspect_ratio: f32,
+    pub znear: f32,
+    pub zfar: f32,
+    pub light_position: Vector3<f32>,
+}
+
+// glTF constants
+const FLOAT: u32 = 5126;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+// position + normal + uv + tangent, the same interleaved layout as Vertex
+const VERTEX_STRIDE: usize = 12*4;
+
+// Writes the scene as a .gltf file and its geometry as a .bin file next to it
+pub fn export_gltf(scene: &GltfScene, path: &Path) -> Result<()> {
+    let bin_path = path.with_extension("bin");
+    let bin_name = bin_path.file_name().and_then(|file_name| file_name.to_str()).unwrap_or_default();
+
+    let buffer = geometry_buffer(scene.mesh);
+    File::create(&bin_path)?.write_all(&buffer)?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(scene_json(scene, buffer.len(), Some(bin_name)).as_bytes())?;
+    writer.flush()
+}
+
+// Writes the scene as a single binary .glb file
+pub fn export_glb(scene: &GltfScene, path: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_glb(&mut writer, scene)?;
+    writer.flush()
+}
+
+pub fn write_glb<W: Write>(writer: &mut W, scene: &GltfScene) -> Result<()> {
+    let mut buffer = geometry_buffer(scene.mesh);
+    let mut json = scene_json(scene, buffer.len(), None).into_bytes();
+    // both chunks must be 4 bytes aligned, the json one is padded with spaces
+    while !json.len().is_multiple_of(4) {
+        json.push(b' ');
+    }
+    while !buffer.len().is_multiple_of(4) {
+        buffer.push(0);
+    }
+
+    let length = 12 + 8 + json.len() + 8 + buffer.len();
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(b"JSON")?;
+    writer.write_all(&json)?;
+
+    writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+    writer.write_all(b"BIN\0")?;
+    writer.write_all(&buffer)
+}
+
+// interleaved vertices followed by the indices
+fn geometry_buffer(mesh: &dyn Mesh) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(mesh.get_vertices().len()*VERTEX_STRIDE + mesh.get_indices().len()*12);
+    for vertex in mesh.get_vertices() {
+        let position = vertex.get_position();
+        let normal = vertex.get_normal();
+        let uv = vertex.get_uv();
+        let tangent = vertex.get_tangent();
+        for value in position.iter().chain(&normal).chain(&uv).chain(&tangent) {
+            buffer.extend(value.to_le_bytes());
+        }
+    }
+    buffer.extend(mesh.get_index_buffer().to_le_bytes());
+    buffer
+}
+
+fn scene_json(scene: &GltfScene, buffer_length: usize, bin_uri: Option<&str>) -> String {
+    let nb_vertices = scene.mesh.get_vertices().len();
+    let index_buffer = scene.mesh.get_index_buffer();
+    let nb_indices = index_buffer.len();
+    let (index_type, index_size) = match index_buffer {
+        IndexBuffer::U16(_) => (UNSIGNED_SHORT, 2),
+        IndexBuffer::U32(_) => (UNSIGNED_INT, 4),
+    };
+    let vertices_length = nb_vertices*VERTEX_STRIDE;
+    let bounds = scene.mesh.get_bounds();
+
+    // one node per cylinder, then the camera & the light
+    let mut nodes: Vec<String> = scene.positions.iter().map(|position| {
+        format!(r#"{{"mesh":0,"translation":[{},{},{}]}}"#, position.x, position.y, position.z)
+    }).collect();
+
+    // the camera node looks down its -z axis with +y up like the view matrix, so its rotation is the inverse of the view one
+    let view = scene.camera.get_view_matrix();
+    let rotation = Quaternion::from(Matrix3::from_cols(view.x.truncate(), view.y.truncate(), view.z.truncate()).transpose());
+    let origin = scene.camera.get_origin();
+    nodes.push(format!(
+        r#"{{"name":"camera","camera":0,"translation":[{},{},{}],"rotation":[{},{},{},{}]}}"#,
+        origin.x, origin.y, origin.z, rotation.v.x, rotation.v.y, rotation.v.z, rotation.s,
+    ));
+    let light = scene.light_position;
+    nodes.push(format!(
+        r#"{{"name":"light","translation":[{},{},{}],"extensions":{{"KHR_lights_punctual":{{"light":0}}}}}}"#,
+        light.x, light.y, light.z,
+    ));
+    let scene_nodes: Vec<String> = (0..nodes.len()).map(|node| node.to_string()).collect();
+
+    let uri = bin_uri.map(|uri| format!(r#""uri":"{}","#, encode_uri(uri))).unwrap_or_default();
+    let Rad(yfov) = Rad::from(scene.camera.get_zoom());
+
+    format!(
+        concat!(
+            "{{",
+            r#""asset":{{"version":"2.0","generator":"CylindersLand"}},"#,
+            r#""extensionsUsed":["KHR_lights_punctual"],"#,
+            r#""extensions":{{"KHR_lights_punctual":{{"lights":[{{"type":"point","color":[1,1,1],"intensity":1}}]}}}},"#,
+            r#""scene":0,"scenes":[{{"nodes":[{nodes_list}]}}],"#,
+            r#""nodes":[{nodes}],"#,
+            r#""cameras":[{{"type":"perspective","perspective":{{"yfov":{yfov},"aspectRatio":{aspect},"znear":{znear},"zfar":{zfar}}}}}],"#,
+            r#""materials":[{{"pbrMetallicRoughness":{{"baseColorFactor":[0.8,0.4,0.65,1],"metallicFactor":0}}}}],"#,
+            r#""meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1,"TEXCOORD_0":2,"TANGENT":3}},"indices":4,"material":0}}]}}],"#,
+            r#""buffers":[{{{uri}"byteLength":{buffer_length}}}],"#,
+            r#""bufferViews":["#,
+            r#"{{"buffer":0,"byteOffset":0,"byteLength":{vertices_length},"byteStride":{stride},"target":{array_buffer}}},"#,
+            r#"{{"buffer":0,"byteOffset":{vertices_length},"byteLength":{indices_length},"target":{element_array_buffer}}}"#,
+            r#"],"#,
+            r#""accessors":["#,
+            r#"{{"bufferView":0,"byteOffset":0,"componentType":{float},"count":{nb_vertices},"type":"VEC3","min":[{min_x},{min_y},{min_z}],"max":[{max_x},{max_y},{max_z}]}},"#,
+            r#"{{"bufferView":0,"byteOffset":12,"componentType":{float},"count":{nb_vertices},"type":"VEC3"}},"#,
+            r#"{{"bufferView":0,"byteOffset":24,"componentType":{float},"count":{nb_vertices},"type":"VEC2"}},"#,
+            r#"{{"bufferView":0,"byteOffset":32,"componentType":{float},"count":{nb_vertices},"type":"VEC4"}},"#,
+            r#"{{"bufferView":1,"byteOffset":0,"componentType":{index_type},"count":{nb_indices},"type":"SCALAR"}}"#,
+            r#"]"#,
+            "}}",
+        ),
+        nodes_list = scene_nodes.join(","),
+        nodes = nodes.join(","),
+        yfov = yfov,
+        aspect = scene.aspect_ratio,
+        znear = scene.znear,
+        zfar = scene.zfar,
+        uri = uri,
+        buffer_length = buffer_length,
+        vertices_length = vertices_length,
+        indices_length = nb_indices*index_size,
+        stride = VERTEX_STRIDE,
+        array_buffer = ARRAY_BUFFER,
+        element_array_buffer = ELEMENT_ARRAY_BUFFER,
+        float = FLOAT,
+        index_type = index_type,
+        nb_vertices = nb_vertices,
+        nb_indices = nb_indices,
+        min_x = bounds.min.x, min_y = bounds.min.y, min_z = bounds.min.z,
+        max_x = bounds.max.x, max_y = bounds.max.y, max_z = bounds.max.z,
+    )
+}
+
+// percent-encodes everything but the unreserved characters, so a file name with spaces or quotes is both a
+// valid URI and a valid JSON string
+fn encode_uri(uri: &str) -> String {
+    uri.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+    use crate::mesh::{TriangleMesh, Vertex};
+
+    fn read_u32(bytes: &[u8], offset: usize) -> usize {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+    }
+
+    // a scene of two instances of the mesh, lit from (0, 5, 0)
+    fn with_scene<T>(mesh: &dyn Mesh, f: impl FnOnce(&GltfScene) -> T) -> T {
+        let camera = Camera::new(Vector3::new(0., 0., 10.), 90., 0., 8.5, 0.1, 45.);
+        let positions = [Vector3::new(0., 0., 0.), Vector3::new(2., 0., 0.)];
+        f(&GltfScene {
+            mesh,
+            positions: &positions,
+            camera: &camera,
+            aspect_ratio: 4./3.,
+            znear: 0.1,
+            zfar: 100.,
+            light_position: Vector3::new(0., 5., 0.),
+        })
+    }
+
+    // (json, binary) chunks of a glb file after checking its header & chunk lengths
+    fn check_glb(mesh: &dyn Mesh) -> (String, Vec<u8>) {
+        let mut file = Vec::new();
+        with_scene(mesh, |scene| write_glb(&mut file, scene)).unwrap();
+
+        assert_eq!(&file[0..4], b"glTF");
+        assert_eq!(read_u32(&file, 4), 2);
+        assert_eq!(read_u32(&file, 8), file.len());
+
+        let json_length = read_u32(&file, 12);
+        assert_eq!(&file[16..20], b"JSON");
+        assert!(json_length.is_multiple_of(4));
+        let json = String::from_utf8(file[20..20 + json_length].to_vec()).unwrap();
+
+        let bin_start = 20 + json_length;
+        let bin_length = read_u32(&file, bin_start);
+        assert_eq!(&file[bin_start + 4..bin_start + 8], b"BIN\0");
+        assert!(bin_length.is_multiple_of(4));
+        assert_eq!(bin_start + 8 + bin_length, file.len());
+        (json, file[bin_start + 8..].to_vec())
+    }
+
+    // values of the "count" of every accessor, in order
+    fn accessor_counts(json: &str) -> Vec<usize> {
+        json.split(r#""count":"#).skip(1)
+            .map(|rest| rest.split(',').next().unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn glb_of_a_cylinder() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let (json, buffer) = check_glb(&cylinder);
+        let (nb_vertices, nb_indices) = (cylinder.get_vertices().len(), 3*cylinder.get_indices().len());
+        assert_eq!(accessor_counts(&json), vec![nb_vertices, nb_vertices, nb_vertices, nb_vertices, nb_indices]);
+        assert!(json.contains(&format!(r#""byteLength":{}"#, geometry_buffer(&cylinder).len())));
+        assert!(buffer.len() >= nb_vertices*VERTEX_STRIDE + 2*nb_indices);
+    }
+
+    #[test]
+    fn glb_chunks_are_padded() {
+        // 3 u16 indices leave the geometry 2 bytes short of a multiple of 4
+        let vertices = [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]
+            .map(|position| Vertex::new(position, [0., 0., 1.], [0., 0.], [1., 0., 0., 1.]))
+            .to_vec();
+        let triangle = TriangleMesh::new(vertices, vec![[0, 1, 2]]);
+        assert_eq!(geometry_buffer(&triangle).len() % 4, 2);
+
+        let (json, buffer) = check_glb(&triangle);
+        assert_eq!(accessor_counts(&json), vec![3, 3, 3, 3, 3]);
+        assert_eq!(buffer.len(), geometry_buffer(&triangle).len() + 2);
+        assert!(buffer.ends_with(&[0, 0]));
+        assert!(json.trim_end().ends_with('}'));
+    }
+
+    #[test]
+    fn gltf_with_a_separate_buffer() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let directory = std::env::temp_dir().join(format!("gltf_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("my \"scene\".gltf");
+        with_scene(&cylinder, |scene| export_gltf(scene, &path)).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        let buffer = std::fs::read(directory.join("my \"scene\".bin")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // the file name is percent-encoded so the json stays valid
+        assert!(json.contains(r#""uri":"my%20%22scene%22.bin""#), "{}", json);
+        assert!(json.contains(&format!(r#""byteLength":{}"#, buffer.len())));
+        assert_eq!(buffer, geometry_buffer(&cylinder));
+        let (nb_vertices, nb_indices) = (cylinder.get_vertices().len(), 3*cylinder.get_indices().len());
+        assert_eq!(accessor_counts(&json), vec![nb_vertices, nb_vertices, nb_vertices, nb_vertices, nb_indices]);
+        assert!(json.contains(r#""name":"light","translation":[0,5,0]"#));
+    }
+}
@@ -0,0 +1,28 @@
+// Meshes, their generators, loaders & exporters, and the geometry queries on them. The viewer in main.rs draws
+// them with OpenGL
+pub mod camera;
+pub mod mesh;
+
+pub mod cylinder;
+pub mod tube;
+pub mod capsule;
+pub mod sphere;
+pub mod torus;
+pub mod plane;
+pub mod cuboid;
+pub mod disc;
+pub mod sweep;
+pub mod lod;
+
+pub mod obj;
+pub mod stl;
+pub mod ply;
+pub mod gltf;
+
+pub mod validate;
+pub mod bounds;
+pub mod mass;
+pub mod ray;
+pub mod bvh;
+pub mod optimize;
+pub mod remesh;
@@ -0,0 +1,123 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Matrix4, Vector3};
+
+use crate::cylinder::Cylinder;
+
+// fraction of the maximum error by which an instance has to go past it before changing level, so that
+// it doesn't pop back & forth when it stays around a switching distance
+const HYSTERESIS: f32 = 0.25;
+
+// The same cylinder at several slice counts, from the finest to the coarsest level. Each instance uses
+// the coarsest level whose silhouette stays within max_error pixels of the round cylinder
+pub struct CylinderLod {
+    levels: Vec<Cylinder>,
+    slice_counts: Vec<usize>,
+    radius: f32,
+    max_error: f32,
+}
+
+impl CylinderLod {
+    pub fn new(slice_counts: &[usize], height: f32, radius: f32, max_error: f32) -> Self {
+        assert!(!slice_counts.is_empty(), "a level of detail needs at least one slice count");
+        let mut slice_counts = slice_counts.to_vec();
+        slice_counts.sort_unstable_by(|a, b| b.cmp(a));
+        slice_counts.dedup();
+        Self {
+            levels: slice_counts.iter().map(|&nb_slices| Cylinder::new(nb_slices, height, radius)).collect(),
+            slice_counts,
+            radius,
+            max_error,
+        }
+    }
+
+    pub fn get_levels(&self) -> &Vec<Cylinder> {
+        &self.levels
+    }
+
+    // radius in pixels of the cylinder centered at center, with proj the projection matrix of the camera
+    pub fn get_projected_radius(&self, proj: &Matrix4<f32>, camera_origin: Vector3<f32>, center: Vector3<f32>, viewport_height: f32) -> f32 {
+        // proj.y.y is 1/tan(fovy/2), which maps a size at distance 1 to normalized device coordinates
+        let distance = (center - camera_origin).magnitude().max(f32::EPSILON);
+        self.radius*proj.y.y/distance*viewport_height/2.
+    }
+
+    // Level to use for an instance which was drawn at current_level on the previous frame. It only goes
+    // finer once the error is above max_error*(1 + HYSTERESIS) and coarser once it is below max_error*(1 - HYSTERESIS)
+    pub fn select_level(&self, current_level: usize, projected_radius: f32) -> usize {
+        // distance in pixels between the middle of a slice and the circle (sagitta of the arc)
+        let error = |level: usize| projected_radius*(1. - (PI/(self.slice_counts[level] as f32)).cos());
+        let coarsest_within = |max_error: f32| {
+            (0..self.levels.len()).rev().find(|&level| error(level) <= max_error).unwrap_or(0)
+        };
+
+        let current_level = current_level.min(self.levels.len() - 1);
+        if error(current_level) > self.max_error*(1. + HYSTERESIS) {
+            coarsest_within(self.max_error)
+        } else {
+            coarsest_within(self.max_error*(1. - HYSTERESIS)).max(current_level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLICE_COUNTS: [usize; 4] = [64, 32, 16, 8];
+
+    // silhouette error per pixel of projected radius
+    fn get_sagitta(nb_slices: usize) -> f32 {
+        1. - (PI/(nb_slices as f32)).cos()
+    }
+
+    // (projected radius, new level) of every level change while the radius goes through the given values
+    fn get_switches(lod: &CylinderLod, start_level: usize, radii: impl Iterator<Item = f32>) -> Vec<(f32, usize)> {
+        let mut level = start_level;
+        let mut switches = Vec::new();
+        for radius in radii {
+            let new_level = lod.select_level(level, radius);
+            if new_level != level {
+                switches.push((radius, new_level));
+                level = new_level;
+            }
+        }
+        switches
+    }
+
+    #[test]
+    fn levels_switch_past_the_hysteresis_band() {
+        let lod = CylinderLod::new(&SLICE_COUNTS, 1., 0.5, 1.);
+        let radii: Vec<f32> = (0..2000).map(|i| 1.01f32.powi(i)).collect();
+
+        // going finer once the current level is max_error*(1 + HYSTERESIS) off, one level at a time here
+        let growing = get_switches(&lod, 3, radii.iter().copied());
+        assert_eq!(growing.iter().map(|&(_, level)| level).collect::<Vec<_>>(), vec![2, 1, 0]);
+        for (&(radius, _), nb_slices) in growing.iter().zip([8, 16, 32]) {
+            let threshold = (1. + HYSTERESIS)/get_sagitta(nb_slices);
+            assert!(radius > threshold && radius <= threshold*1.01, "finer at {} instead of {}", radius, threshold);
+        }
+
+        // going coarser once the coarser level is within max_error*(1 - HYSTERESIS)
+        let shrinking = get_switches(&lod, 0, radii.iter().rev().copied());
+        assert_eq!(shrinking.iter().map(|&(_, level)| level).collect::<Vec<_>>(), vec![1, 2, 3]);
+        for (&(radius, _), nb_slices) in shrinking.iter().zip([32, 16, 8]) {
+            let threshold = (1. - HYSTERESIS)/get_sagitta(nb_slices);
+            assert!(radius <= threshold && radius > threshold/1.01, "coarser at {} instead of {}", radius, threshold);
+        }
+    }
+
+    #[test]
+    fn levels_do_not_flip_inside_the_band() {
+        let lod = CylinderLod::new(&SLICE_COUNTS, 1., 0.5, 1.);
+        // between the two switches of the 16 & 8 slices levels
+        let (low, high) = ((1. - HYSTERESIS)/get_sagitta(8), (1. + HYSTERESIS)/get_sagitta(8));
+        let oscillating = (0..100).map(|i| if i%2 == 0 {low*1.05} else {high*0.95});
+        assert!(get_switches(&lod, 3, oscillating.clone()).is_empty());
+        assert!(get_switches(&lod, 2, oscillating).is_empty());
+
+        // a large jump goes straight to the right level
+        assert_eq!(lod.select_level(3, 1000.), 0);
+        assert_eq!(lod.select_level(0, 1.), 3);
+    }
+}
@@ -2,16 +2,19 @@ use cgmath::{Deg, Matrix4, Rad, SquareMatrix, Vector2, Vector3};
 use glfw::{ffi::glfwGetTime, Action, Context, Key, MouseButton};
 use std::mem::{size_of, size_of_val};
 
-mod camera;
-use camera::{Camera, Direction};
-
 mod shader;
 use shader::Shader;
 
-mod cylinder;
-use cylinder::Cylinder;
-
-use crate::cylinder::Vertex;
+use hello_triangle::bounds::Aabb;
+use hello_triangle::bvh::Bvh;
+use hello_triangle::camera::{Camera, Direction};
+use hello_triangle::gltf::{self, GltfScene};
+use hello_triangle::lod::CylinderLod;
+use hello_triangle::mesh::{IndexBuffer, Mesh, Vertex};
+use hello_triangle::obj;
+use hello_triangle::optimize::optimize_mesh;
+use hello_triangle::ray::Ray;
+use hello_triangle::validate;
 
 pub fn upload_data<T>(buffer_type: gl::types::GLenum, data: &[T], usage: gl::types::GLenum) {
     unsafe {
@@ -24,6 +27,108 @@ pub fn upload_data<T>(buffer_type: gl::types::GLenum, data: &[T], usage: gl::typ
     }
 }
 
+// gl objects of an uploaded mesh & what DrawElements needs to draw it
+pub struct GpuMesh {
+    vao: u32,
+    vbo: u32,
+    ebo: u32,
+    nb_indices: i32,
+    index_type: gl::types::GLenum,
+}
+
+// uploads the mesh into a new vao, with 16 bits indices when possible
+pub fn upload_mesh(mesh: &dyn Mesh) -> GpuMesh {
+    // setting up vbo (vertex buffer object) and vao (vertex array object)
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+        assert_ne!(vao, 0);
+        gl::BindVertexArray(vao);
+    }
+
+    let mut vbo = 0;
+    unsafe {
+        gl::GenBuffers(1, &mut vbo);
+        assert_ne!(vbo, 0);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        // copying our vertices into the vbo (bounded to the GL_ARRAY_BUFFER)
+        upload_data(gl::ARRAY_BUFFER, &mesh.get_vertices(), gl::STATIC_DRAW);
+
+        // how OpenGL should interpret the data inside the vbo currently bounded to GL_ARRAY_BUFFER = position attribute
+        gl::VertexAttribPointer(
+            0,         // location of the vertex attribute we want to configure
+            3,         // each position is vec3 so 3 values
+            gl::FLOAT, // each value (coordinate) is a float
+            gl::FALSE,
+            size_of::<Vertex>().try_into().unwrap(),
+            0 as *const _,
+        );
+        // enabling the vertex attribut by giving its location
+        gl::EnableVertexAttribArray(0);
+
+        // normal attribute
+        gl::VertexAttribPointer(
+            1,         // location of the vertex attribute we want to configure
+            3,         // each position is vec3 so 3 values
+            gl::FLOAT, // each value (coordinate) is a float
+            gl::FALSE,
+            size_of::<Vertex>().try_into().unwrap(),
+            (std::mem::size_of::<[f32; 3]>()) as *const std::ffi::c_void,
+        );
+        gl::EnableVertexAttribArray(1);
+
+        // texture coordinates attribute
+        gl::VertexAttribPointer(
+            2,         // location of the vertex attribute we want to configure
+            2,         // each uv is vec2 so 2 values
+            gl::FLOAT, // each value (coordinate) is a float
+            gl::FALSE,
+            size_of::<Vertex>().try_into().unwrap(),
+            (2 * std::mem::size_of::<[f32; 3]>()) as *const std::ffi::c_void,
+        );
+        gl::EnableVertexAttribArray(2);
+
+        // tangent attribute (xyz + handedness)
+        gl::VertexAttribPointer(
+            3,         // location of the vertex attribute we want to configure
+            4,         // each tangent is vec4 so 4 values
+            gl::FLOAT, // each value (coordinate) is a float
+            gl::FALSE,
+            size_of::<Vertex>().try_into().unwrap(),
+            (2 * std::mem::size_of::<[f32; 3]>() + std::mem::size_of::<[f32; 2]>()) as *const std::ffi::c_void,
+        );
+        gl::EnableVertexAttribArray(3);
+    }
+
+    // setting up ebo (element buffer object)
+    let index_buffer = mesh.get_index_buffer();
+    let mut ebo = 0;
+    unsafe {
+        gl::GenBuffers(1, &mut ebo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+        match &index_buffer {
+            IndexBuffer::U16(indices) => upload_data(gl::ELEMENT_ARRAY_BUFFER, indices, gl::STATIC_DRAW),
+            IndexBuffer::U32(indices) => upload_data(gl::ELEMENT_ARRAY_BUFFER, indices, gl::STATIC_DRAW),
+        }
+
+        //gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+    }
+
+    GpuMesh {
+        vao,
+        vbo,
+        ebo,
+        nb_indices: index_buffer.len().try_into().unwrap(),
+        index_type: index_buffer.gl_type(),
+    }
+}
+
+// position frag.glsl moves the light to at the given time, whatever u_lightpos is
+fn get_light_position(time: f64) -> Vector3<f32> {
+    let angle = (time as f32)*0.2;
+    Vector3::new(0., angle.cos()*10., angle.sin()*10.)
+}
+
 fn main() {
     // indicates if we allow camera movement w/ mouse or not
     let mut camera_move = false;
@@ -59,8 +164,10 @@ fn main() {
     let mut current_mouse: Vector2<f64> = Vector2 { x: 0., y: 0. };
     let mut last_mouse: Option<Vector2<f64>> = None;
 
-    // setting up our vertices of our triangle (in NDC coordinates) for cylinder object
-    let cyl: Cylinder = Cylinder::new(10, 1., 0.5);
+    // setting up our vertices of our triangle (in NDC coordinates) for cylinder object, at several levels of
+    // detail so that the silhouette stays within half a pixel of a round one
+    let (cyl_height, cyl_radius) = (1., 0.5);
+    let cyl_lod = CylinderLod::new(&[64, 32, 16, 8], cyl_height, cyl_radius, 0.5);
 
     // cylinder pos
     let cyl_positions = [
@@ -76,71 +183,44 @@ fn main() {
         Vector3::new(-1.3, 1.0, -1.5),
     ];
 
-    // setting up vbo (vertex buffer object) and vao (vertex array object)
-    let mut vao = 0;
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao);
-        assert_ne!(vao, 0);
-        gl::BindVertexArray(vao);
-    }
-
-    let mut vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut vbo);
-        assert_ne!(vbo, 0);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        // copying our vertices into the vbo (bounded to the GL_ARRAY_BUFFER)
-        upload_data(gl::ARRAY_BUFFER, &cyl.get_vertices(), gl::STATIC_DRAW);
-
-        // how OpenGL should interpret the data inside the vbo currently bounded to GL_ARRAY_BUFFER = position attribute
-        gl::VertexAttribPointer(
-            0,         // location of the vertex attribute we want to configure
-            3,         // each position is vec3 so 3 values
-            gl::FLOAT, // each value (coordinate) is a float
-            gl::FALSE,
-            size_of::<Vertex>().try_into().unwrap(),
-            0 as *const _,
-        );
-        // enabling the vertex attribut by giving its location
-        gl::EnableVertexAttribArray(0);
-
-        // normal attribute
-        gl::VertexAttribPointer(
-            1,         // location of the vertex attribute we want to configure
-            3,         // each position is vec3 so 3 values
-            gl::FLOAT, // each value (coordinate) is a float
-            gl::FALSE,
-            size_of::<Vertex>().try_into().unwrap(),
-            (std::mem::size_of::<[f32; 3]>()) as *const std::ffi::c_void,
-        );
-        gl::EnableVertexAttribArray(1);
-    }
-
-    // setting up ebo (element buffer object)
-    let mut ebo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut ebo);
-        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-        upload_data(
-            gl::ELEMENT_ARRAY_BUFFER,
-            &cyl.get_indices(),
-            gl::STATIC_DRAW,
-        );
+    // welded & reordered for the vertex cache as they are drawn many times per frame
+    let cyl_gpus: Vec<GpuMesh> = cyl_lod.get_levels().iter().map(|level| upload_mesh(&optimize_mesh(level, 1e-5).0)).collect();
+    // level each cylinder was drawn at on the previous frame, for the hysteresis
+    let mut cyl_levels = vec![0; cyl_positions.len()];
 
-        //gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+    // hierarchy over the boxes of the ideal cylinders, for picking
+    let cyl_aabb = Aabb {
+        min: Vector3::new(-cyl_radius, -cyl_height/2., -cyl_radius),
+        max: Vector3::new(cyl_radius, cyl_height/2., cyl_radius),
+    };
+    let cyl_bvh = Bvh::new(&cyl_positions.map(|cyl_pos| cyl_aabb.transform(&Matrix4::from_translation(cyl_pos))));
+
+    // OBJ parts given on the command line, drawn at the origin among the cylinders
+    let mut parts = Vec::new();
+    for path in std::env::args().skip(1) {
+        match obj::import_obj(std::path::Path::new(&path)) {
+            Ok(part) => {
+                let report = validate::validate_mesh(&part);
+                if !report.is_valid() {
+                    println!("WARNING::OBJ_VALIDATION of {}: {}", path, report);
+                }
+                let (optimized, optimization) = optimize_mesh(&part, 1e-5);
+                println!("INFO::OBJ_OPTIMIZATION of {}: {}", path, optimization);
+                parts.push(upload_mesh(&optimized));
+            },
+            Err(error) => println!("ERROR::OBJ_IMPORT of {}: {}", path, error),
+        }
     }
 
     // building the shader program
     let shader_pgrm: Shader = Shader::new("./src/shaders/vertex.glsl", "./src/shaders/frag.glsl");
     shader_pgrm.use_program();
-    shader_pgrm.set_uniform_3float(
-        "u_lightpos",
-        Vector3 {
-            x: 0.0,
-            y: 5.0,
-            z: 0.0,
-        },
-    );
+    let light_pos = Vector3 {
+        x: 0.0,
+        y: 5.0,
+        z: 0.0,
+    };
+    shader_pgrm.set_uniform_3float("u_lightpos", light_pos);
     // enabling depth test
     unsafe {
         gl::Enable(gl::DEPTH_TEST);
@@ -187,19 +267,36 @@ fn main() {
             shader_pgrm.set_uniform_2float("u_resolution", resolution);
         }
 
-        for cyl_pos in cyl_positions {
-            shader_pgrm.set_uniform_mat4("model", Matrix4::from_translation(cyl_pos));
+        for (cyl_pos, cyl_level) in cyl_positions.iter().zip(cyl_levels.iter_mut()) {
+            let projected_radius = cyl_lod.get_projected_radius(&proj, camera.get_origin(), *cyl_pos, resolution[1]);
+            *cyl_level = cyl_lod.select_level(*cyl_level, projected_radius);
+            let cyl_gpu = &cyl_gpus[*cyl_level];
+
+            shader_pgrm.set_uniform_mat4("model", Matrix4::from_translation(*cyl_pos));
             unsafe {
+                gl::BindVertexArray(cyl_gpu.vao);
                 // mode = primitive we would like to draw, first = starting index of the vao we'd like to draw
                 // count = how many vertices are used in the EBO (3 per triangle, for ex)
                 gl::DrawElements(
                     gl::TRIANGLES,
-                    (cyl.get_indices().len() * 3).try_into().unwrap(),
-                    gl::UNSIGNED_INT,
+                    cyl_gpu.nb_indices,
+                    cyl_gpu.index_type,
                     0 as *const _,
                 );
             }
         }
+        for part in &parts {
+            shader_pgrm.set_uniform_mat4("model", Matrix4::identity());
+            unsafe {
+                gl::BindVertexArray(part.vao);
+                gl::DrawElements(
+                    gl::TRIANGLES,
+                    part.nb_indices,
+                    part.index_type,
+                    std::ptr::null(),
+                );
+            }
+        }
         // drawing triangle
 
         // poll for and process events
@@ -226,9 +323,40 @@ fn main() {
                     camera.process_keyboard(Direction::FWD, delta_time as f32);
                 }
 
+                // snapshot of the scene as a glTF file
+                glfw::WindowEvent::Key(Key::G, _, Action::Press, _) => {
+                    let scene = GltfScene {
+                        mesh: &cyl_lod.get_levels()[0],
+                        positions: &cyl_positions,
+                        camera: &camera,
+                        aspect_ratio: resolution[0] / resolution[1],
+                        znear: 0.1,
+                        zfar: 100.0,
+                        light_position: get_light_position(glfw.get_time()),
+                    };
+                    match gltf::export_gltf(&scene, std::path::Path::new("./scene.gltf")) {
+                        Ok(()) => println!("Scene exported to ./scene.gltf"),
+                        Err(error) => println!("ERROR::GLTF_EXPORT: {}", error),
+                    }
+                }
+
                 glfw::WindowEvent::MouseButton(glfw::MouseButtonLeft, Action::Press, _) => {
                     camera_move = !camera_move;
                 }
+
+                // picking of the cylinder under the cursor
+                glfw::WindowEvent::MouseButton(glfw::MouseButtonRight, Action::Press, _) => {
+                    let (x, y) = window.get_cursor_pos();
+                    let ray = Ray::from_screen(Vector2::new(x as f32, y as f32), resolution, &proj, &view);
+                    let intersect = |i: usize| ray.intersect_cylinder(cyl_height, cyl_radius, &Matrix4::from_translation(cyl_positions[i]));
+                    let hit = cyl_bvh
+                        .find_closest(|aabb| ray.intersect_aabb(aabb), |i| intersect(i).map(|hit| hit.distance))
+                        .and_then(|(i, _)| intersect(i).map(|hit| (i, hit)));
+                    match hit {
+                        Some((i, hit)) => println!("Cylinder {} hit at {:?} ({} away), normal {:?}", i, hit.point, hit.distance, hit.normal),
+                        None => println!("No cylinder under the cursor"),
+                    }
+                }
                 glfw::WindowEvent::Scroll(_, y) => {
                     camera.process_scroll(y);
                 }
@@ -257,7 +385,10 @@ fn main() {
     }
 
     unsafe {
-        gl::DeleteVertexArrays(1, &vao);
-        gl::DeleteBuffers(1, &vbo);
+        for gpu_mesh in cyl_gpus.iter().chain(&parts) {
+            gl::DeleteVertexArrays(1, &gpu_mesh.vao);
+            gl::DeleteBuffers(1, &gpu_mesh.vbo);
+            gl::DeleteBuffers(1, &gpu_mesh.ebo);
+        }
     }
 }
@@ -0,0 +1,148 @@
+use cgmath::{InnerSpace, Matrix3, Vector3};
+
+use crate::mesh::{Mesh, Vertex};
+
+// Geometric properties of a closed mesh wound counter-clockwise seen from outside, as a solid of density 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MassProperties {
+    pub area: f32,
+    // also the mass
+    pub volume: f32,
+    // center of mass of the solid
+    pub centroid: Vector3<f32>,
+    // inertia tensor about the centroid, multiply by the density to get the physical one
+    pub inertia: Matrix3<f32>,
+}
+
+pub fn mesh_mass_properties(mesh: &dyn Mesh) -> MassProperties {
+    mass_properties(mesh.get_vertices(), mesh.get_indices())
+}
+
+// Integrals of 1, x, y, z, x², y², z², xy, yz & zx over the volume, turned into surface integrals by the
+// divergence theorem so each triangle adds the contribution of the prism between it and the yz plane (Eberly,
+// "Polyhedral Mass Properties"). They are accumulated in f64 as they mostly cancel out on a closed mesh
+pub fn mass_properties(vertices: &[Vertex], indices: &[[u32; 3]]) -> MassProperties {
+    let mut area = 0.;
+    let mut integrals = [0f64; 10];
+    for triangle in indices {
+        let [p0, p1, p2] = triangle.map(|index| Vector3::from(vertices[index as usize].get_position()).cast::<f64>().unwrap());
+        let d = (p1 - p0).cross(p2 - p0);
+        area += d.magnitude()/2.;
+
+        let mut f1 = [0.; 3];
+        let mut f2 = [0.; 3];
+        let mut f3 = [0.; 3];
+        let mut g = [[0.; 3]; 3];
+        for axis in 0..3 {
+            let (w0, w1, w2) = (p0[axis], p1[axis], p2[axis]);
+            let temp0 = w0 + w1;
+            f1[axis] = temp0 + w2;
+            let temp1 = w0*w0;
+            let temp2 = temp1 + w1*temp0;
+            f2[axis] = temp2 + w2*f1[axis];
+            f3[axis] = w0*temp1 + w1*temp2 + w2*f2[axis];
+            g[axis] = [w0, w1, w2].map(|w| f2[axis] + w*(f1[axis] + w));
+        }
+
+        integrals[0] += d.x*f1[0];
+        for axis in 0..3 {
+            integrals[1 + axis] += d[axis]*f2[axis];
+            integrals[4 + axis] += d[axis]*f3[axis];
+        }
+        // products xy, yz & zx, each one integrated along the axis of its first factor
+        integrals[7] += d.x*(p0.y*g[0][0] + p1.y*g[0][1] + p2.y*g[0][2]);
+        integrals[8] += d.y*(p0.z*g[1][0] + p1.z*g[1][1] + p2.z*g[1][2]);
+        integrals[9] += d.z*(p0.x*g[2][0] + p1.x*g[2][1] + p2.x*g[2][2]);
+    }
+    integrals[0] /= 6.;
+    for integral in &mut integrals[1..4] {
+        *integral /= 24.;
+    }
+    for integral in &mut integrals[4..7] {
+        *integral /= 60.;
+    }
+    for integral in &mut integrals[7..10] {
+        *integral /= 120.;
+    }
+
+    let volume = integrals[0];
+    // an open or flat mesh has no volume, its center stays at the origin
+    let centroid = if volume.abs() > f64::EPSILON {
+        Vector3::new(integrals[1], integrals[2], integrals[3])/volume
+    } else {
+        Vector3::new(0., 0., 0.)
+    };
+    let (x, y, z) = (centroid.x, centroid.y, centroid.z);
+
+    // second moments moved from the origin to the centroid (parallel axis theorem)
+    let ixx = integrals[5] + integrals[6] - volume*(y*y + z*z);
+    let iyy = integrals[4] + integrals[6] - volume*(z*z + x*x);
+    let izz = integrals[4] + integrals[5] - volume*(x*x + y*y);
+    let ixy = -(integrals[7] - volume*x*y);
+    let iyz = -(integrals[8] - volume*y*z);
+    let izx = -(integrals[9] - volume*z*x);
+
+    MassProperties {
+        area: area as f32,
+        volume: volume as f32,
+        centroid: centroid.cast().unwrap(),
+        inertia: Matrix3::new(
+            ixx, ixy, izx,
+            ixy, iyy, iyz,
+            izx, iyz, izz,
+        ).cast().unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::cylinder::Cylinder;
+
+    fn check_relative(value: f32, expected: f32, tolerance: f32, name: &str, nb_slices: usize) {
+        let error = (value - expected).abs()/expected;
+        assert!(error < tolerance, "{} slices: {} {} instead of {} ({} > {})", nb_slices, name, value, expected, error, tolerance);
+    }
+
+    // the inscribed prisms get closer to the cylinder by O(1/n²), e.g. its volume goes from 1.4142 with 8 slices
+    // to 1.5706 with 256 for π·r²·h = 1.5708
+    #[test]
+    fn prisms_converge_to_the_cylinder() {
+        let (height, radius) = (2., 0.5);
+        let volume = PI*radius*radius*height;
+        let area = 2.*PI*radius*radius + 2.*PI*radius*height;
+        // about the axis, then about the diameters through the centroid
+        let axial_inertia = volume*radius*radius/2.;
+        let transverse_inertia = volume*(3.*radius*radius + height*height)/12.;
+
+        let mut previous_error = f32::MAX;
+        for nb_slices in [8, 16, 32, 64, 128, 256] {
+            let properties = mesh_mass_properties(&Cylinder::new(nb_slices, height, radius));
+            let tolerance = 16./(nb_slices*nb_slices) as f32;
+            check_relative(properties.volume, volume, tolerance, "volume", nb_slices);
+            check_relative(properties.area, area, tolerance, "area", nb_slices);
+            check_relative(properties.inertia.y.y, axial_inertia, tolerance, "Iyy", nb_slices);
+            check_relative(properties.inertia.x.x, transverse_inertia, tolerance, "Ixx", nb_slices);
+            check_relative(properties.inertia.z.z, transverse_inertia, tolerance, "Izz", nb_slices);
+            assert!(properties.centroid.magnitude() < 1e-5, "{} slices: centroid {:?}", nb_slices, properties.centroid);
+            // the axes of the cylinder are its principal axes
+            for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+                assert!(properties.inertia[i][j].abs() < 1e-5*axial_inertia);
+            }
+
+            let error = (properties.volume - volume).abs();
+            assert!(error < previous_error);
+            previous_error = error;
+        }
+    }
+
+    #[test]
+    fn offset_cylinder_centroid() {
+        let (start, end) = (Vector3::new(1., 2., -3.), Vector3::new(4., -1., 2.));
+        let properties = mesh_mass_properties(&Cylinder::between(64, start, end, 0.5));
+        assert!((properties.centroid - (start + end)/2.).magnitude() < 1e-4, "centroid {:?}", properties.centroid);
+        check_relative(properties.volume, PI*0.25*(end - start).magnitude(), 16./(64*64) as f32, "volume", 64);
+    }
+}
@@ -0,0 +1,194 @@
+use std::ops::Deref;
+
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use crate::bounds::Aabb;
+
+type vtx = [f32; 3];
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Vertex {
+    vertex: vtx,
+    normal: vtx,
+    uv: [f32; 2],
+    // xyz = tangent (direction of increasing u), w = handedness of the (tangent, bitangent, normal) frame
+    tangent: [f32; 4],
+}
+
+impl Vertex {
+    pub fn new(v: vtx, n: vtx, uv: [f32; 2], t: [f32; 4]) -> Self {
+        Self {
+            vertex:v,
+            normal:n,
+            uv,
+            tangent:t,
+        }
+    }
+
+    pub fn get_position(&self) -> vtx {
+        self.vertex
+    }
+
+    pub fn get_normal(&self) -> vtx {
+        self.normal
+    }
+
+    pub fn get_uv(&self) -> [f32; 2] {
+        self.uv
+    }
+
+    pub fn get_tangent(&self) -> [f32; 4] {
+        self.tangent
+    }
+
+    // bitangent = (normal x tangent) * handedness, i.e. the direction of increasing v
+    pub fn get_bitangent(&self) -> vtx {
+        let (n, t) = (self.normal, self.tangent);
+        [
+            (n[1]*t[2] - n[2]*t[1]) * t[3],
+            (n[2]*t[0] - n[0]*t[2]) * t[3],
+            (n[0]*t[1] - n[1]*t[0]) * t[3],
+        ]
+    }
+}
+
+// Common interface of every generated or loaded shape, what the renderer & exporters need
+pub trait Mesh {
+    fn get_vertices(&self) -> &Vec<Vertex>;
+
+    fn get_indices(&self) -> &Vec<[u32; 3]>;
+
+    // indices as they are uploaded to the GPU, see IndexBuffer
+    fn get_index_buffer(&self) -> IndexBuffer {
+        IndexBuffer::new(self.get_indices(), self.get_vertices().len())
+    }
+
+    fn get_bounds(&self) -> Aabb {
+        Aabb::from_vertices(self.get_vertices())
+    }
+}
+
+// Vertices & triangles of a shape, what the loaders & the mesh processing return
+#[derive(Clone, Debug, Default)]
+pub struct TriangleMesh {
+    vertices: Vec<Vertex>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl TriangleMesh {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<[u32; 3]>) -> Self {
+        Self {
+            vertices,
+            indices,
+        }
+    }
+}
+
+impl Mesh for TriangleMesh {
+    fn get_vertices(&self) -> &Vec<Vertex> {
+        &self.vertices
+    }
+
+    fn get_indices(&self) -> &Vec<[u32; 3]> {
+        &self.indices
+    }
+}
+
+// the generated shapes wrap a TriangleMesh and dereference to it
+impl<T: Deref<Target = TriangleMesh>> Mesh for T {
+    fn get_vertices(&self) -> &Vec<Vertex> {
+        self.deref().get_vertices()
+    }
+
+    fn get_indices(&self) -> &Vec<[u32; 3]> {
+        self.deref().get_indices()
+    }
+}
+
+// triangle of the vertices at these indices
+pub(crate) fn triangle(a: usize, b: usize, c: usize) -> [u32; 3] {
+    [a.try_into().unwrap(), b.try_into().unwrap(), c.try_into().unwrap()]
+}
+
+// Flat list of indices, 16 bits wide when every vertex can be addressed with them so small meshes take
+// half the memory, 32 bits wide otherwise
+pub enum IndexBuffer {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl IndexBuffer {
+    pub fn new(triangles: &[[u32; 3]], nb_vertices: usize) -> Self {
+        let indices = triangles.iter().flatten();
+        if nb_vertices <= u16::MAX as usize + 1 {
+            IndexBuffer::U16(indices.map(|&index| index as u16).collect())
+        } else {
+            IndexBuffer::U32(indices.copied().collect())
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            IndexBuffer::U16(indices) => indices.len(),
+            IndexBuffer::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // type to give to DrawElements
+    pub fn gl_type(&self) -> gl::types::GLenum {
+        match self {
+            IndexBuffer::U16(_) => gl::UNSIGNED_SHORT,
+            IndexBuffer::U32(_) => gl::UNSIGNED_INT,
+        }
+    }
+
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            IndexBuffer::U16(indices) => indices.iter().flat_map(|index| index.to_le_bytes()).collect(),
+            IndexBuffer::U32(indices) => indices.iter().flat_map(|index| index.to_le_bytes()).collect(),
+        }
+    }
+}
+
+// Per-vertex tangents of any triangle list: the uv gradients of the triangles around each vertex are summed
+// then made orthogonal to its normal. Vertices without usable uvs get any tangent orthogonal to their normal
+pub fn generate_tangents(positions: &[[f32; 3]], normals: &[[f32; 3]], uvs: &[[f32; 2]], indices: &[[u32; 3]]) -> Vec<[f32; 4]> {
+    let mut tangents = vec![Vector3::zero(); positions.len()];
+    let mut bitangents = vec![Vector3::zero(); positions.len()];
+
+    for triangle in indices {
+        let [a, b, c] = triangle.map(|index| index as usize);
+        let edge1 = Vector3::from(positions[b]) - Vector3::from(positions[a]);
+        let edge2 = Vector3::from(positions[c]) - Vector3::from(positions[a]);
+        let (du1, dv1) = (uvs[b][0] - uvs[a][0], uvs[b][1] - uvs[a][1]);
+        let (du2, dv2) = (uvs[c][0] - uvs[a][0], uvs[c][1] - uvs[a][1]);
+
+        let det = du1*dv2 - du2*dv1;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1*dv2 - edge2*dv1) / det;
+        let bitangent = (edge2*du1 - edge1*du2) / det;
+        for index in [a, b, c] {
+            tangents[index] += tangent;
+            bitangents[index] += bitangent;
+        }
+    }
+
+    (0..positions.len()).map(|i| {
+        let normal = Vector3::from(normals[i]);
+        let mut tangent = tangents[i] - normal*normal.dot(tangents[i]);
+        if tangent.magnitude2() < f32::EPSILON {
+            let axis = if normal.x.abs() < 0.9 {Vector3::unit_x()} else {Vector3::unit_y()};
+            tangent = axis - normal*normal.dot(axis);
+        }
+        let tangent = tangent.normalize();
+        let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0. {-1.} else {1.};
+        [tangent.x, tangent.y, tangent.z, handedness]
+    }).collect()
+}
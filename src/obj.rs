@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use crate::mesh::{generate_tangents, triangle, Mesh, TriangleMesh, Vertex};
+
+// Material written to the companion .mtl file, the default one matches the color & specular term of frag.glsl
+pub struct Material {
+    pub name: String,
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            diffuse: [0.8, 0.40, 0.65],
+            specular: [0.5, 0.5, 0.5],
+            shininess: 32.,
+        }
+    }
+}
+
+// Writes the mesh as a Wavefront OBJ file, and the material as a .mtl file next to it if given
+pub fn export_obj(mesh: &dyn Mesh, path: &Path, material: Option<&Material>) -> io::Result<()> {
+    let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("mesh");
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    match material {
+        Some(material) => {
+            let mtl_path = path.with_extension("mtl");
+            let mut mtl_writer = BufWriter::new(File::create(&mtl_path)?);
+            write_mtl(&mut mtl_writer, material)?;
+            mtl_writer.flush()?;
+
+            let mtl_name = mtl_path.file_name().and_then(|file_name| file_name.to_str()).unwrap_or_default();
+            write_obj(&mut writer, mesh, name, Some((mtl_name, material.name.as_str())))?;
+        },
+        None => write_obj(&mut writer, mesh, name, None)?,
+    }
+    writer.flush()
+}
+
+// OBJ content of the mesh, material is the (.mtl file, material name) to reference
+pub fn write_obj<W: Write>(writer: &mut W, mesh: &dyn Mesh, name: &str, material: Option<(&str, &str)>) -> io::Result<()> {
+    writeln!(writer, "# {} vertices, {} triangles", mesh.get_vertices().len(), mesh.get_indices().len())?;
+    if let Some((mtl_file, _)) = material {
+        writeln!(writer, "mtllib {}", mtl_file)?;
+    }
+    writeln!(writer, "o {}", name)?;
+
+    // every vertex has a position, a texture coordinate & a normal so the three share the same index
+    for vertex in mesh.get_vertices() {
+        let [x, y, z] = vertex.get_position();
+        writeln!(writer, "v {} {} {}", x, y, z)?;
+    }
+    for vertex in mesh.get_vertices() {
+        let [u, v] = vertex.get_uv();
+        writeln!(writer, "vt {} {}", u, v)?;
+    }
+    for vertex in mesh.get_vertices() {
+        let [x, y, z] = vertex.get_normal();
+        writeln!(writer, "vn {} {} {}", x, y, z)?;
+    }
+
+    if let Some((_, material_name)) = material {
+        writeln!(writer, "usemtl {}", material_name)?;
+    }
+    // OBJ indices start at 1
+    for triangle in mesh.get_indices() {
+        let [a, b, c] = triangle.map(|index| index + 1);
+        writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+    }
+    Ok(())
+}
+
+pub fn write_mtl<W: Write>(writer: &mut W, material: &Material) -> io::Result<()> {
+    let ([dr, dg, db], [sr, sg, sb]) = (material.diffuse, material.specular);
+    writeln!(writer, "newmtl {}", material.name)?;
+    writeln!(writer, "Ka 0 0 0")?;
+    writeln!(writer, "Kd {} {} {}", dr, dg, db)?;
+    writeln!(writer, "Ks {} {} {}", sr, sg, sb)?;
+    writeln!(writer, "Ns {}", material.shininess)?;
+    writeln!(writer, "illum 2")
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    // malformed line, with its number starting at 1
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(error) => write!(f, "{}", error),
+            ObjError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<io::Error> for ObjError {
+    fn from(error: io::Error) -> Self {
+        ObjError::Io(error)
+    }
+}
+
+pub fn import_obj(path: &Path) -> Result<TriangleMesh, ObjError> {
+    parse_obj(BufReader::new(File::open(path)?))
+}
+
+// Reads the v, vt, vn & f statements, the other ones (groups, materials, smoothing...) are ignored.
+// Polygons are triangulated, and normals are generated from the faces when the file doesn't give any
+pub fn parse_obj<R: BufRead>(reader: R) -> Result<TriangleMesh, ObjError> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    // a vertex is created for every distinct (position, uv, normal) combination used by the faces
+    let mut corners: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+    let mut corner_list: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+    let mut indices: Vec<[u32; 3]> = Vec::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = line_index + 1;
+        let error = |message: String| ObjError::Parse { line: line_number, message };
+
+        // comments can also end a line
+        let content = line.split('#').next().unwrap_or_default();
+        let mut tokens = content.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let values = parse_floats(&arguments, 3, 3, keyword).map_err(error)?;
+                positions.push([values[0], values[1], values[2]]);
+            },
+            "vt" => {
+                // v is optional and defaults to 0
+                let values = parse_floats(&arguments, 1, 2, keyword).map_err(error)?;
+                uvs.push([values[0], values.get(1).copied().unwrap_or(0.)]);
+            },
+            "vn" => {
+                let values = parse_floats(&arguments, 3, 3, keyword).map_err(error)?;
+                normals.push([values[0], values[1], values[2]]);
+            },
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error(format!("a face needs at least 3 vertices, found {}", arguments.len())));
+                }
+                let mut face = Vec::with_capacity(arguments.len());
+                for argument in &arguments {
+                    let corner = parse_corner(argument, positions.len(), uvs.len(), normals.len()).map_err(error)?;
+                    let index = *corners.entry(corner).or_insert_with(|| {
+                        corner_list.push(corner);
+                        corner_list.len() - 1
+                    });
+                    face.push(index);
+                }
+
+                let points: Vec<Vector3<f32>> = face.iter().map(|&index| Vector3::from(positions[corner_list[index].0])).collect();
+                for [a, b, c] in triangulate(&points) {
+                    indices.push(triangle(face[a], face[b], face[c]));
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // area weighted face normals summed per position, so they are smooth across uv seams
+    let mut generated_normals = vec![Vector3::zero(); positions.len()];
+    if corner_list.iter().any(|corner| corner.2.is_none()) {
+        for triangle in &indices {
+            let [a, b, c] = triangle.map(|index| corner_list[index as usize].0);
+            let (pa, pb, pc) = (Vector3::from(positions[a]), Vector3::from(positions[b]), Vector3::from(positions[c]));
+            let face_normal = (pb - pa).cross(pc - pa);
+            for position in [a, b, c] {
+                generated_normals[position] += face_normal;
+            }
+        }
+    }
+
+    let vertex_positions: Vec<[f32; 3]> = corner_list.iter().map(|corner| positions[corner.0]).collect();
+    let vertex_uvs: Vec<[f32; 2]> = corner_list.iter().map(|corner| corner.1.map_or([0., 0.], |uv| uvs[uv])).collect();
+    let vertex_normals: Vec<[f32; 3]> = corner_list.iter().map(|corner| {
+        let normal = match corner.2 {
+            Some(normal) => Vector3::from(normals[normal]),
+            None => generated_normals[corner.0],
+        };
+        if normal.magnitude2() > 0. {normal.normalize().into()} else {[0., 1., 0.]}
+    }).collect();
+    let tangents = generate_tangents(&vertex_positions, &vertex_normals, &vertex_uvs, &indices);
+
+    let vertices = (0..corner_list.len())
+        .map(|i| Vertex::new(vertex_positions[i], vertex_normals[i], vertex_uvs[i], tangents[i]))
+        .collect();
+
+    Ok(TriangleMesh::new(vertices, indices))
+}
+
+// between min & max first values of a v, vt or vn statement, the extra ones (w, vertex colors...) are ignored
+fn parse_floats(arguments: &[&str], min: usize, max: usize, keyword: &str) -> Result<Vec<f32>, String> {
+    if arguments.len() < min {
+        let plural = if min > 1 {"s"} else {""};
+        return Err(format!("'{}' needs at least {} value{}, found {}", keyword, min, plural, arguments.len()));
+    }
+    arguments.iter().take(max)
+        .map(|argument| argument.parse::<f32>().map_err(|_| format!("invalid number '{}' in '{}'", argument, keyword)))
+        .collect()
+}
+
+// v, v/vt, v//vn or v/vt/vn with indices starting at 1, or negative ones relative to the end of the lists
+fn parse_corner(argument: &str, nb_positions: usize, nb_uvs: usize, nb_normals: usize) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let parts: Vec<&str> = argument.split('/').collect();
+    if parts.len() > 3 {
+        return Err(format!("invalid face vertex '{}'", argument));
+    }
+    let resolve = |part: &str, count: usize, kind: &str| -> Result<usize, String> {
+        let index: i64 = part.parse().map_err(|_| format!("invalid {} index '{}' in '{}'", kind, part, argument))?;
+        let resolved = if index < 0 {count as i64 + index} else {index - 1};
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("{} index {} out of range, there are {} of them", kind, index, count));
+        }
+        Ok(resolved as usize)
+    };
+
+    let position = resolve(parts[0], nb_positions, "position")?;
+    let uv = match parts.get(1) {
+        Some(part) if !part.is_empty() => Some(resolve(part, nb_uvs, "texture coordinate")?),
+        _ => None,
+    };
+    let normal = match parts.get(2) {
+        Some(part) if !part.is_empty() => Some(resolve(part, nb_normals, "normal")?),
+        _ => None,
+    };
+    Ok((position, uv, normal))
+}
+
+// Triangles of a planar polygon by ear clipping, as indices of its corners. The polygon doesn't have
+// to be convex, the triangles keep its winding
+fn triangulate(points: &[Vector3<f32>]) -> Vec<[usize; 3]> {
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // twice the area vector of the polygon
+    let mut normal = Vector3::zero();
+    for i in 0..points.len() {
+        normal += points[i].cross(points[(i + 1) % points.len()]);
+    }
+    let is_left = |a: Vector3<f32>, b: Vector3<f32>, p: Vector3<f32>| (b - a).cross(p - a).dot(normal) >= 0.;
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let nb_remaining = remaining.len();
+        let ear = (0..nb_remaining).find(|&k| {
+            let (i_a, i_b, i_c) = (remaining[(k + nb_remaining - 1) % nb_remaining], remaining[k], remaining[(k + 1) % nb_remaining]);
+            let (a, b, c) = (points[i_a], points[i_b], points[i_c]);
+            let convex = (b - a).cross(c - b).dot(normal) > 0.;
+            convex && remaining.iter()
+                .filter(|&&i| i != i_a && i != i_b && i != i_c)
+                .all(|&i| !(is_left(a, b, points[i]) && is_left(b, c, points[i]) && is_left(c, a, points[i])))
+        });
+
+        match ear {
+            Some(k) => {
+                triangles.push([remaining[(k + nb_remaining - 1) % nb_remaining], remaining[k], remaining[(k + 1) % nb_remaining]]);
+                remaining.remove(k);
+            },
+            // degenerate polygon (collinear or self intersecting), the rest is split as a fan
+            None => break,
+        }
+    }
+    for k in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[k], remaining[k + 1]]);
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+
+    #[test]
+    fn round_trip_keeps_the_triangles() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let mut file = Vec::new();
+        write_obj(&mut file, &cylinder, "cylinder", None).unwrap();
+        let mesh = parse_obj(file.as_slice()).unwrap();
+
+        // the vertices are renumbered in the order the faces use them, so they are compared through the corners
+        assert_eq!(mesh.get_indices().len(), cylinder.get_indices().len());
+        let corners = mesh.get_indices().iter().flatten().zip(cylinder.get_indices().iter().flatten());
+        for (&index, &original_index) in corners {
+            let (vertex, original) = (mesh.get_vertices()[index as usize], cylinder.get_vertices()[original_index as usize]);
+            assert_eq!(vertex.get_position(), original.get_position());
+            assert_eq!(vertex.get_uv(), original.get_uv());
+            // the normals read are normalized again
+            assert!((Vector3::from(vertex.get_normal()) - Vector3::from(original.get_normal())).magnitude() < 1e-6);
+            // OBJ has no tangents, they are generated again from the uvs
+            let ([x, y, z, w], [ox, oy, oz, ow]) = (vertex.get_tangent(), original.get_tangent());
+            assert!(Vector3::new(x, y, z).dot(Vector3::new(ox, oy, oz)) > 0.99);
+            assert_eq!(w, ow);
+        }
+        // every vertex of the cylinder is used by a face, so none is lost or duplicated
+        assert_eq!(mesh.get_vertices().len(), cylinder.get_vertices().len());
+    }
+
+    fn parse(content: &str) -> TriangleMesh {
+        parse_obj(content.as_bytes()).unwrap()
+    }
+
+    // triangle normal following the winding, scaled by twice its area
+    fn face_normal(mesh: &TriangleMesh, triangle: [u32; 3]) -> Vector3<f32> {
+        let [a, b, c] = triangle.map(|index| Vector3::from(mesh.get_vertices()[index as usize].get_position()));
+        (b - a).cross(c - a)
+    }
+
+    #[test]
+    fn quad_is_split_in_two() {
+        let quad = parse(include_str!("../tests/fixtures/quad.obj"));
+        assert_eq!(quad.get_vertices().len(), 4);
+        assert_eq!(quad.get_indices().len(), 2);
+        for &triangle in quad.get_indices() {
+            assert!(face_normal(&quad, triangle).normalize().dot(Vector3::unit_y()) > 0.9999);
+        }
+        assert_eq!(quad.get_vertices()[2].get_uv(), [1., 1.]);
+    }
+
+    #[test]
+    fn concave_polygon_is_ear_clipped() {
+        let arrow = parse(include_str!("../tests/fixtures/concave.obj"));
+        assert_eq!(arrow.get_indices().len(), 4);
+        // every triangle keeps the winding of the polygon and together they cover its area once
+        let mut area = 0.;
+        for &triangle in arrow.get_indices() {
+            let normal = face_normal(&arrow, triangle);
+            assert!(normal.z > 0., "{:?} is flipped", triangle);
+            area += normal.magnitude()/2.;
+        }
+        assert!((area - 4.).abs() < 1e-5, "area {}", area);
+        for vertex in arrow.get_vertices() {
+            assert_eq!(vertex.get_normal(), [0., 0., 1.]);
+            assert_eq!(vertex.get_uv(), [0., 0.]);
+        }
+    }
+
+    #[test]
+    fn negative_indices() {
+        let mesh = parse(include_str!("../tests/fixtures/negative.obj"));
+        assert_eq!(mesh.get_indices(), &vec![[0, 1, 2], [0, 2, 3]]);
+        let positions: Vec<[f32; 3]> = mesh.get_vertices().iter().map(|vertex| vertex.get_position()).collect();
+        assert_eq!(positions, vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]]);
+        let uvs: Vec<[f32; 2]> = mesh.get_vertices().iter().map(|vertex| vertex.get_uv()).collect();
+        assert_eq!(uvs, vec![[0., 0.], [1., 0.], [1., 1.], [0., 1.]]);
+    }
+
+    #[test]
+    fn missing_normals_are_generated() {
+        let tetrahedron = parse(include_str!("../tests/fixtures/no_normals.obj"));
+        assert_eq!(tetrahedron.get_indices().len(), 4);
+        let center = Vector3::new(0.25, 0.25, 0.25);
+        for vertex in tetrahedron.get_vertices() {
+            let normal = Vector3::from(vertex.get_normal());
+            assert!((normal.magnitude() - 1.).abs() < 1e-5);
+            assert!(normal.dot(Vector3::from(vertex.get_position()) - center) > 0.);
+            // smooth across the uv seams: the vertices at the same position share their normal
+            for other in tetrahedron.get_vertices() {
+                if other.get_position() == vertex.get_position() {
+                    assert_eq!(other.get_normal(), vertex.get_normal());
+                }
+            }
+        }
+        let origin = tetrahedron.get_vertices().iter().find(|vertex| vertex.get_position() == [0., 0., 0.]).unwrap();
+        let expected = -Vector3::new(1., 1., 1.).normalize();
+        assert!((Vector3::from(origin.get_normal()) - expected).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn parse_errors_give_the_line() {
+        let triangle = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n";
+        let cases = [
+            ("# header\nv 1 2\n", 2, "'v' needs at least 3 values, found 2"),
+            ("vt\n", 1, "'vt' needs at least 1 value, found 0"),
+            ("vn 0 1\n", 1, "'vn' needs at least 3 values, found 2"),
+            ("v 0 0 0\nvt 0 x\n", 2, "invalid number 'x' in 'vt'"),
+            (&format!("{}f 1 2\n", triangle), 6, "a face needs at least 3 vertices, found 2"),
+            (&format!("{}f 1/1/1/1 2 3\n", triangle), 6, "invalid face vertex '1/1/1/1'"),
+            (&format!("{}f a 2 3\n", triangle), 6, "invalid position index 'a' in 'a'"),
+            (&format!("{}f 1/b 2 3\n", triangle), 6, "invalid texture coordinate index 'b' in '1/b'"),
+            (&format!("{}f 1//c 2 3\n", triangle), 6, "invalid normal index 'c' in '1//c'"),
+            (&format!("{}\nf 0 2 3\n", triangle), 7, "position index 0 out of range, there are 3 of them"),
+            (&format!("{}f 1 2 4\n", triangle), 6, "position index 4 out of range, there are 3 of them"),
+            (&format!("{}f -4 2 3\n", triangle), 6, "position index -4 out of range, there are 3 of them"),
+            (&format!("{}f 1/2 2/1 3/1\n", triangle), 6, "texture coordinate index 2 out of range, there are 1 of them"),
+            (&format!("{}f 1//1 2//1 3//-2\n", triangle), 6, "normal index -2 out of range, there are 1 of them"),
+        ];
+        for (content, expected_line, expected_message) in cases {
+            match parse_obj(content.as_bytes()) {
+                Err(ObjError::Parse { line, message }) => {
+                    assert_eq!((line, message.as_str()), (expected_line, expected_message), "{:?}", content);
+                },
+                result => panic!("{:?} parsed as {:?}", content, result),
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::mesh::{Mesh, TriangleMesh, Vertex};
+
+// size of the post-transform vertex cache the triangles are ordered for, a FIFO of that many vertices
+const CACHE_SIZE: usize = 16;
+
+// largest difference between the normals, uvs & tangents of two vertices that can be welded, so hard edges & uv
+// seams are kept
+const ATTRIBUTE_EPSILON: f32 = 1e-4;
+
+// The average cache miss ratio (ACMR) is the number of vertices the GPU has to transform per triangle, between
+// 0.5 for the best possible order of a large regular mesh & 3 when no vertex is reused
+#[derive(Debug)]
+pub struct OptimizationReport {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub triangles_before: usize,
+    pub triangles_after: usize,
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {} vertices, {} -> {} triangles, ACMR {:.3} -> {:.3}",
+            self.vertices_before, self.vertices_after, self.triangles_before, self.triangles_after,
+            self.acmr_before, self.acmr_after,
+        )
+    }
+}
+
+// Welds the vertices closer than epsilon, then reorders the triangles for the vertex cache & the vertices in
+// the order the triangles use them so they are fetched sequentially
+pub fn optimize_mesh(mesh: &dyn Mesh, epsilon: f32) -> (TriangleMesh, OptimizationReport) {
+    let (vertices, indices) = weld_vertices(mesh.get_vertices(), mesh.get_indices(), epsilon);
+    let indices = optimize_vertex_cache(&indices, vertices.len());
+    let (vertices, indices) = optimize_vertex_fetch(&vertices, &indices);
+
+    let report = OptimizationReport {
+        vertices_before: mesh.get_vertices().len(),
+        vertices_after: vertices.len(),
+        triangles_before: mesh.get_indices().len(),
+        triangles_after: indices.len(),
+        acmr_before: get_acmr(mesh.get_indices()),
+        acmr_after: get_acmr(&indices),
+    };
+    (TriangleMesh::new(vertices, indices), report)
+}
+
+// Merges the vertices whose positions are within epsilon of each other and whose other attributes are the same.
+// The triangles left with twice the same vertex are removed
+pub fn weld_vertices(vertices: &[Vertex], indices: &[[u32; 3]], epsilon: f32) -> (Vec<Vertex>, Vec<[u32; 3]>) {
+    let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() <= ATTRIBUTE_EPSILON);
+    let (welded, remap) = get_weld_remap(vertices, epsilon, |a, b| {
+        close(&a.get_normal(), &b.get_normal()) && close(&a.get_uv(), &b.get_uv()) && close(&a.get_tangent(), &b.get_tangent())
+    });
+    let indices = indices.iter()
+        .map(|triangle| triangle.map(|index| remap[index as usize]))
+        .filter(|[a, b, c]| a != b && b != c && c != a)
+        .collect();
+    (welded, indices)
+}
+
+// Vertices left once the ones within epsilon of an earlier one (by position) for which same is true are merged
+// into it, and the new index of each vertex. They are looked for in the neighboring cells of a grid of epsilon
+// wide cells
+pub(crate) fn get_weld_remap(vertices: &[Vertex], epsilon: f32, same: impl Fn(&Vertex, &Vertex) -> bool) -> (Vec<Vertex>, Vec<u32>) {
+    let epsilon = epsilon.max(f32::MIN_POSITIVE);
+    let cell = |position: [f32; 3]| position.map(|x| (x/epsilon).floor() as i64);
+    let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() <= epsilon);
+
+    let mut welded: Vec<Vertex> = Vec::new();
+    let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let remap = vertices.iter().map(|vertex| {
+        let [x, y, z] = cell(vertex.get_position());
+        // the cells saturate far from the origin with a tiny epsilon, the lookups then just miss
+        let neighbor_cells = (-1..=1).flat_map(|dx| (-1..=1).flat_map(move |dy| {
+            (-1..=1).map(move |dz| [x.wrapping_add(dx), y.wrapping_add(dy), z.wrapping_add(dz)])
+        }));
+        let existing = neighbor_cells
+            .filter_map(|neighbor_cell| grid.get(&neighbor_cell))
+            .flatten()
+            .find(|&&index| close(welded[index].get_position(), vertex.get_position()) && same(&welded[index], vertex))
+            .copied();
+        let index = existing.unwrap_or_else(|| {
+            welded.push(*vertex);
+            grid.entry([x, y, z]).or_default().push(welded.len() - 1);
+            welded.len() - 1
+        });
+        index.try_into().unwrap()
+    }).collect();
+    (welded, remap)
+}
+
+// Tipsify (Sander, Nehab & Barczak, "Fast Triangle Reordering for Vertex Locality and Reduced Overdraw"): emits
+// all the triangles around a fanning vertex, then moves to the neighbor that will still be in the cache after
+// its own triangles are emitted, or back to a recently used vertex when there is none
+pub fn optimize_vertex_cache(indices: &[[u32; 3]], nb_vertices: usize) -> Vec<[u32; 3]> {
+    // triangles around each vertex
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); nb_vertices];
+    for (triangle_index, triangle) in indices.iter().enumerate() {
+        for &index in triangle {
+            adjacency[index as usize].push(triangle_index);
+        }
+    }
+    // number of triangles around each vertex not emitted yet
+    let mut live: Vec<usize> = adjacency.iter().map(|triangles| triangles.len()).collect();
+    // time at which each vertex entered the cache
+    let mut cache_time = vec![0; nb_vertices];
+    let mut emitted = vec![false; indices.len()];
+    let mut dead_ends: Vec<usize> = Vec::new();
+    let mut time = CACHE_SIZE + 1;
+    let mut cursor = 0;
+
+    let mut reordered = Vec::with_capacity(indices.len());
+    let mut fanning = if nb_vertices > 0 {Some(0)} else {None};
+    while let Some(vertex) = fanning {
+        let mut candidates = Vec::new();
+        for &triangle_index in &adjacency[vertex] {
+            if emitted[triangle_index] {
+                continue;
+            }
+            let triangle = indices[triangle_index];
+            for index in triangle.map(|index| index as usize) {
+                dead_ends.push(index);
+                candidates.push(index);
+                live[index] -= 1;
+                if time - cache_time[index] > CACHE_SIZE {
+                    cache_time[index] = time;
+                    time += 1;
+                }
+            }
+            emitted[triangle_index] = true;
+            reordered.push(triangle);
+        }
+
+        // the candidate that entered the cache the earliest while staying in it after its triangles are emitted.
+        // The ones without triangles left or that would leave the cache have no priority and are never taken
+        let mut best: Option<(usize, usize)> = None;
+        for &candidate in &candidates {
+            if live[candidate] == 0 {
+                continue;
+            }
+            let age = time - cache_time[candidate];
+            let priority = if age + 2*live[candidate] <= CACHE_SIZE {age} else {0};
+            if priority > 0 && best.is_none_or(|(_, best_priority)| priority > best_priority) {
+                best = Some((candidate, priority));
+            }
+        }
+        fanning = best.map(|(candidate, _)| candidate).or_else(|| {
+            // dead end: back to the last vertices used, or the next one in the input with triangles left
+            while let Some(dead_end) = dead_ends.pop() {
+                if live[dead_end] > 0 {
+                    return Some(dead_end);
+                }
+            }
+            while cursor < nb_vertices {
+                if live[cursor] > 0 {
+                    return Some(cursor);
+                }
+                cursor += 1;
+            }
+            None
+        });
+    }
+    reordered
+}
+
+// Renumbers the vertices in the order the triangles first use them so they are read sequentially from memory,
+// the vertices used by no triangle are dropped
+pub fn optimize_vertex_fetch(vertices: &[Vertex], indices: &[[u32; 3]]) -> (Vec<Vertex>, Vec<[u32; 3]>) {
+    let mut remap: Vec<Option<u32>> = vec![None; vertices.len()];
+    let mut reordered = Vec::new();
+    let indices = indices.iter().map(|triangle| triangle.map(|index| {
+        *remap[index as usize].get_or_insert_with(|| {
+            reordered.push(vertices[index as usize]);
+            (reordered.len() - 1).try_into().unwrap()
+        })
+    })).collect();
+    (reordered, indices)
+}
+
+// average cache miss ratio of the triangles with a FIFO cache of CACHE_SIZE vertices
+pub fn get_acmr(indices: &[[u32; 3]]) -> f32 {
+    if indices.is_empty() {
+        return 0.;
+    }
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 1);
+    let mut misses = 0;
+    for &index in indices.iter().flatten() {
+        if !cache.contains(&index) {
+            misses += 1;
+            cache.push(index);
+            if cache.len() > CACHE_SIZE {
+                cache.remove(0);
+            }
+        }
+    }
+    misses as f32/indices.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+    use crate::sphere::UvSphere;
+    use crate::validate::validate_mesh;
+
+    #[test]
+    fn welding_keeps_the_hard_edges() {
+        // the seam of the side wall has two uvs and the cap rims two normals, so nothing is welded
+        let cylinder = Cylinder::new(16, 1., 0.5);
+        let (vertices, indices) = weld_vertices(cylinder.get_vertices(), cylinder.get_indices(), 1e-5);
+        assert_eq!(vertices.len(), cylinder.get_vertices().len());
+        assert_eq!(indices.len(), cylinder.get_indices().len());
+
+        // the vertices split per triangle are all welded back, even with a null epsilon
+        let split: Vec<Vertex> = cylinder.get_indices().iter().flatten().map(|&index| cylinder.get_vertices()[index as usize]).collect();
+        let split_indices: Vec<[u32; 3]> = (0..cylinder.get_indices().len() as u32).map(|i| [3*i, 3*i + 1, 3*i + 2]).collect();
+        let (vertices, indices) = weld_vertices(&split, &split_indices, 0.);
+        assert_eq!(vertices.len(), cylinder.get_vertices().len());
+        assert_eq!(indices.len(), cylinder.get_indices().len());
+    }
+
+    #[test]
+    fn optimizing_lowers_the_acmr() {
+        let sphere = UvSphere::new(64, 32, 1.);
+        let (optimized, report) = optimize_mesh(&sphere, 1e-6);
+        assert!(report.acmr_after < report.acmr_before, "{}", report);
+        assert!(report.acmr_after < 0.8, "{}", report);
+        assert_eq!(report.triangles_after, optimized.get_indices().len());
+        assert_eq!(report.vertices_after, optimized.get_vertices().len());
+        assert!(validate_mesh(&optimized).is_valid());
+    }
+
+    #[test]
+    fn reordering_emits_every_triangle_once() {
+        // the dead ends are reached on every mesh, at least at the end of each connected part
+        let meshes = [Cylinder::builder(48, 1., 0.5).stacks(12).build(), Cylinder::new(3, 1., 0.5)];
+        for mesh in &meshes {
+            let mut reordered = optimize_vertex_cache(mesh.get_indices(), mesh.get_vertices().len());
+            let mut original = mesh.get_indices().clone();
+            reordered.sort();
+            original.sort();
+            assert_eq!(reordered, original);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::ops::Deref;
+
+use crate::mesh::{triangle, TriangleMesh, Vertex};
+
+// Flat grid in the xz plane facing +y
+pub struct Plane(TriangleMesh);
+
+impl Deref for Plane {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl Plane {
+    // width along x, depth along z, each split in the given number of quads
+    pub fn new(width: f32, depth: f32, nb_width: usize, nb_depth: usize) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        Self::push_grid(&mut vertices, &mut indices, [0., 0., 0.], [1., 0., 0.], [0., 0., -1.], [width, depth], [nb_width, nb_depth]);
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+
+    // Flat rectangle centered on center, u follows u_axis and v follows v_axis. The normal is u_axis x v_axis
+    // so the quads are counter-clockwise seen from the side it faces
+    pub(crate) fn push_grid(vertices: &mut Vec<Vertex>, indices: &mut Vec<[u32; 3]>, center: [f32; 3], u_axis: [f32; 3], v_axis: [f32; 3], size: [f32; 2], nb_quads: [usize; 2]) {
+        let normal = [
+            u_axis[1]*v_axis[2] - u_axis[2]*v_axis[1],
+            u_axis[2]*v_axis[0] - u_axis[0]*v_axis[2],
+            u_axis[0]*v_axis[1] - u_axis[1]*v_axis[0],
+        ];
+        let tangent = [u_axis[0], u_axis[1], u_axis[2], 1.];
+        let (nb_u, nb_v) = (nb_quads[0].max(1), nb_quads[1].max(1));
+        let start = vertices.len();
+
+        for j in 0..=nb_v {
+            let v = (j as f32)/(nb_v as f32);
+            for i in 0..=nb_u {
+                let u = (i as f32)/(nb_u as f32);
+                let (du, dv) = ((u - 0.5)*size[0], (v - 0.5)*size[1]);
+                let point = [
+                    center[0] + du*u_axis[0] + dv*v_axis[0],
+                    center[1] + du*u_axis[1] + dv*v_axis[1],
+                    center[2] + du*u_axis[2] + dv*v_axis[2],
+                ];
+                vertices.push(Vertex::new(point, normal, [u, v], tangent));
+            }
+        }
+
+        for j in 0..nb_v {
+            for i in 0..nb_u {
+                let index = start + j*(nb_u + 1) + i;
+                let above = index + nb_u + 1;
+                indices.push(triangle(index, index + 1, above + 1));
+                indices.push(triangle(index, above + 1, above));
+            }
+        }
+    }
+}
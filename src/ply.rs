@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::path::Path;
+
+use crate::mesh::Mesh;
+
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+// Additional per-vertex property, with one value per vertex of the mesh
+pub enum PlyProperty<'a> {
+    // written as the red, green & blue uchar properties
+    Colors(&'a [[u8; 3]]),
+    // float property with the given name, e.g. a temperature or a curvature
+    Scalar(&'a str, &'a [f32]),
+}
+
+impl PlyProperty<'_> {
+    fn len(&self) -> usize {
+        match self {
+            PlyProperty::Colors(colors) => colors.len(),
+            PlyProperty::Scalar(_, values) => values.len(),
+        }
+    }
+}
+
+pub fn export_ply(mesh: &dyn Mesh, path: &Path, format: PlyFormat, properties: &[PlyProperty]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_ply(&mut writer, mesh, format, properties)?;
+    writer.flush()
+}
+
+// Vertices with their position, normal, texture coordinates & the additional properties, then triangles
+pub fn write_ply<W: Write>(writer: &mut W, mesh: &dyn Mesh, format: PlyFormat, properties: &[PlyProperty]) -> Result<()> {
+    let vertices = mesh.get_vertices();
+    if properties.iter().any(|property| property.len() != vertices.len()) {
+        return Err(Error::new(ErrorKind::InvalidInput, "every PLY property needs one value per vertex"));
+    }
+    // a property name is a single token of the header
+    if properties.iter().any(|property| matches!(property, PlyProperty::Scalar(name, _) if name.is_empty() || name.contains(char::is_whitespace))) {
+        return Err(Error::new(ErrorKind::InvalidInput, "PLY property names can't be empty or contain whitespace"));
+    }
+
+    writeln!(writer, "ply")?;
+    match format {
+        PlyFormat::Ascii => writeln!(writer, "format ascii 1.0")?,
+        PlyFormat::BinaryLittleEndian => writeln!(writer, "format binary_little_endian 1.0")?,
+    }
+    writeln!(writer, "element vertex {}", vertices.len())?;
+    for name in ["x", "y", "z", "nx", "ny", "nz", "s", "t"] {
+        writeln!(writer, "property float {}", name)?;
+    }
+    for property in properties {
+        match property {
+            PlyProperty::Colors(_) => {
+                for name in ["red", "green", "blue"] {
+                    writeln!(writer, "property uchar {}", name)?;
+                }
+            },
+            PlyProperty::Scalar(name, _) => writeln!(writer, "property float {}", name)?,
+        }
+    }
+    writeln!(writer, "element face {}", mesh.get_indices().len())?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for (i, vertex) in vertices.iter().enumerate() {
+        let mut floats = Vec::from(vertex.get_position());
+        floats.extend(vertex.get_normal());
+        floats.extend(vertex.get_uv());
+
+        match format {
+            PlyFormat::Ascii => {
+                let mut line: Vec<String> = floats.iter().map(|value| value.to_string()).collect();
+                for property in properties {
+                    match property {
+                        PlyProperty::Colors(colors) => line.extend(colors[i].iter().map(|value| value.to_string())),
+                        PlyProperty::Scalar(_, values) => line.push(values[i].to_string()),
+                    }
+                }
+                writeln!(writer, "{}", line.join(" "))?;
+            },
+            PlyFormat::BinaryLittleEndian => {
+                for value in floats {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                for property in properties {
+                    match property {
+                        PlyProperty::Colors(colors) => writer.write_all(&colors[i])?,
+                        PlyProperty::Scalar(_, values) => writer.write_all(&values[i].to_le_bytes())?,
+                    }
+                }
+            },
+        }
+    }
+
+    for triangle in mesh.get_indices() {
+        match format {
+            PlyFormat::Ascii => writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?,
+            PlyFormat::BinaryLittleEndian => {
+                writer.write_all(&[3u8])?;
+                for index in triangle {
+                    writer.write_all(&index.to_le_bytes())?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+
+    #[test]
+    fn ascii_header_and_counts() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let nb_vertices = cylinder.get_vertices().len();
+        let nb_triangles = cylinder.get_indices().len();
+        let curvature = vec![0.; nb_vertices];
+        let mut file = Vec::new();
+        write_ply(&mut file, &cylinder, PlyFormat::Ascii, &[PlyProperty::Scalar("curvature", &curvature)]).unwrap();
+
+        let text = String::from_utf8(file).unwrap();
+        let (header, body) = text.split_once("end_header\n").unwrap();
+        assert!(header.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(header.contains(&format!("element vertex {}\n", nb_vertices)));
+        assert!(header.contains(&format!("element face {}\n", nb_triangles)));
+        assert!(header.contains("property float curvature\n"));
+
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines.len(), nb_vertices + nb_triangles);
+        assert!(lines[..nb_vertices].iter().all(|line| line.split(' ').count() == 9));
+        assert!(lines[nb_vertices..].iter().all(|line| line.starts_with("3 ") && line.split(' ').count() == 4));
+    }
+
+    #[test]
+    fn binary_length() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let nb_vertices = cylinder.get_vertices().len();
+        let nb_triangles = cylinder.get_indices().len();
+        let colors = vec![[255, 0, 0]; nb_vertices];
+        let mut file = Vec::new();
+        write_ply(&mut file, &cylinder, PlyFormat::BinaryLittleEndian, &[PlyProperty::Colors(&colors)]).unwrap();
+
+        let header_end = file.windows(11).position(|window| window == b"end_header\n").unwrap() + 11;
+        assert!(file.starts_with(b"ply\nformat binary_little_endian 1.0\n"));
+        // 8 floats & 3 uchars per vertex, a uchar count & 3 uints per face
+        assert_eq!(file.len() - header_end, nb_vertices*(8*4 + 3) + nb_triangles*(1 + 3*4));
+    }
+
+    #[test]
+    fn invalid_property_names() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let values = vec![0.; cylinder.get_vertices().len()];
+        for name in ["", "mean curvature", "curvature\n"] {
+            let result = write_ply(&mut Vec::new(), &cylinder, PlyFormat::Ascii, &[PlyProperty::Scalar(name, &values)]);
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+    }
+}
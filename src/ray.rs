@@ -0,0 +1,240 @@
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Vector2, Vector3, Vector4};
+
+use crate::bounds::Aabb;
+
+// Half line going through origin + t*direction for t >= 0. The hit distances are values of t, i.e. actual
+// distances when the direction is normalized
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub distance: f32,
+    pub point: Vector3<f32>,
+    // points out of the surface, even when the ray starts inside
+    pub normal: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
+        Self {
+            origin,
+            direction,
+        }
+    }
+
+    // ray going from the camera through the given pixel (from the top left corner of the window), e.g. for picking
+    pub fn from_screen(cursor: Vector2<f32>, resolution: Vector2<f32>, proj: &Matrix4<f32>, view: &Matrix4<f32>) -> Self {
+        let x = 2.*cursor.x/resolution.x - 1.;
+        let y = 1. - 2.*cursor.y/resolution.y;
+        let inverse = (proj*view).invert().expect("the view projection matrix isn't invertible");
+        // the points of the pixel on the near and far planes
+        let unproject = |z: f32| {
+            let point = inverse*Vector4::new(x, y, z, 1.);
+            point.truncate()/point.w
+        };
+        let near = unproject(-1.);
+        Self::new(near, (unproject(1.) - near).normalize())
+    }
+
+    pub fn get_point(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.direction*distance
+    }
+
+    // the direction isn't normalized again so that the distances along the ray stay the same
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        Self::new((matrix*self.origin.extend(1.)).truncate(), (matrix*self.direction.extend(0.)).truncate())
+    }
+
+    // distance at which the ray enters the box (0 when it starts inside) by the slab method
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut entry = 0f32;
+        let mut exit = f32::INFINITY;
+        for axis in 0..3 {
+            // a direction component of 0 gives infinite distances, the slab is then either always or never crossed
+            let inverse = 1./self.direction[axis];
+            let near = (aabb.min[axis] - self.origin[axis])*inverse;
+            let far = (aabb.max[axis] - self.origin[axis])*inverse;
+            entry = entry.max(near.min(far));
+            exit = exit.min(near.max(far));
+        }
+        if entry <= exit {Some(entry)} else {None}
+    }
+
+    // distance to the triangle from either side (Möller & Trumbore)
+    pub fn intersect_triangle(&self, [a, b, c]: [Vector3<f32>; 3]) -> Option<f32> {
+        let (ab, ac) = (b - a, c - a);
+        let p = self.direction.cross(ac);
+        let determinant = ab.dot(p);
+        // the ray is parallel to the triangle
+        if determinant.abs() < f32::EPSILON*ab.magnitude()*ac.magnitude()*self.direction.magnitude() {
+            return None;
+        }
+        // barycentric coordinates of the hit
+        let to_origin = self.origin - a;
+        let u = to_origin.dot(p)/determinant;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+        let q = to_origin.cross(ab);
+        let v = self.direction.dot(q)/determinant;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+        let distance = ac.dot(q)/determinant;
+        if distance >= 0. {Some(distance)} else {None}
+    }
+
+    // Closest hit with the ideal capped cylinder of the given height & radius built by Cylinder::new (along y,
+    // centered on the origin) placed by transform. The ray is brought into the space of the cylinder, where
+    // the side wall is a quadric & the caps are planes, which keeps the same distances along it
+    pub fn intersect_cylinder(&self, height: f32, radius: f32, transform: &Matrix4<f32>) -> Option<RayHit> {
+        let inverse = transform.invert()?;
+        let Ray {origin, direction} = self.transform(&inverse);
+        let half_height = height/2.;
+
+        let mut closest: Option<(f32, Vector3<f32>)> = None;
+        let mut keep = |distance: f32, normal: Vector3<f32>| {
+            if distance >= 0. && closest.is_none_or(|(closest_distance, _)| distance < closest_distance) {
+                closest = Some((distance, normal));
+            }
+        };
+
+        // side wall: x² + z² = radius² between the caps
+        let a = direction.x*direction.x + direction.z*direction.z;
+        let b = 2.*(origin.x*direction.x + origin.z*direction.z);
+        let c = origin.x*origin.x + origin.z*origin.z - radius*radius;
+        let discriminant = b*b - 4.*a*c;
+        // a ray parallel to the axis only meets the caps
+        if a > f32::EPSILON && discriminant >= 0. {
+            let root = discriminant.sqrt();
+            // numerically stable roots, avoiding the cancellation of -b + root
+            let q = -0.5*(b + b.signum()*root);
+            for distance in [q/a, c/q] {
+                let point = origin + direction*distance;
+                if distance.is_finite() && point.y.abs() <= half_height {
+                    keep(distance, Vector3::new(point.x, 0., point.z)/radius);
+                }
+            }
+        }
+
+        // caps: y = ±height/2 inside the radius
+        if direction.y.abs() > f32::EPSILON {
+            for sign in [1., -1.] {
+                let distance = (sign*half_height - origin.y)/direction.y;
+                let point = origin + direction*distance;
+                if point.x*point.x + point.z*point.z <= radius*radius {
+                    keep(distance, Vector3::new(0., sign, 0.));
+                }
+            }
+        }
+
+        closest.map(|(distance, normal)| RayHit {
+            distance,
+            point: self.get_point(distance),
+            normal: (get_normal_matrix(&inverse)*normal).normalize(),
+        })
+    }
+}
+
+// matrix bringing local normals to world space from the inverse of the model matrix: normals go through the
+// inverse transpose so they stay perpendicular to the surface under a non uniform scale
+pub(crate) fn get_normal_matrix(inverse: &Matrix4<f32>) -> Matrix3<f32> {
+    Matrix3::from_cols(inverse.x.truncate(), inverse.y.truncate(), inverse.z.truncate()).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((value - expected).magnitude() < 1e-5, "{:?} instead of {:?}", value, expected);
+    }
+
+    fn assert_hit(hit: Option<RayHit>, distance: f32, point: Vector3<f32>, normal: Vector3<f32>) {
+        let hit = hit.expect("the ray misses");
+        assert!((hit.distance - distance).abs() < 1e-5, "distance {} instead of {}", hit.distance, distance);
+        assert_close(hit.point, point);
+        assert_close(hit.normal, normal);
+    }
+
+    // cylinder of height 2 & radius 1 around the y axis, from y = -1 to 1
+    fn intersect_unit_cylinder(origin: Vector3<f32>, direction: Vector3<f32>) -> Option<RayHit> {
+        Ray::new(origin, direction).intersect_cylinder(2., 1., &Matrix4::identity())
+    }
+
+    #[test]
+    fn cylinder_side_and_caps() {
+        // side wall
+        let hit = intersect_unit_cylinder(Vector3::new(-5., 0.3, 0.), Vector3::unit_x());
+        assert_hit(hit, 4., Vector3::new(-1., 0.3, 0.), -Vector3::unit_x());
+        // top cap
+        let hit = intersect_unit_cylinder(Vector3::new(0.2, 5., 0.1), -Vector3::unit_y());
+        assert_hit(hit, 4., Vector3::new(0.2, 1., 0.1), Vector3::unit_y());
+        // along the axis inside the radius, only the caps are met
+        let hit = intersect_unit_cylinder(Vector3::new(0.5, -5., 0.), Vector3::unit_y());
+        assert_hit(hit, 4., Vector3::new(0.5, -1., 0.), -Vector3::unit_y());
+        assert!(intersect_unit_cylinder(Vector3::new(1.5, -5., 0.), Vector3::unit_y()).is_none());
+        // from the inside the exit point is hit, with the normal still pointing out
+        let hit = intersect_unit_cylinder(Vector3::new(0., 0., 0.), Vector3::unit_z());
+        assert_hit(hit, 1., Vector3::new(0., 0., 1.), Vector3::unit_z());
+        // above the rim & behind the ray
+        assert!(intersect_unit_cylinder(Vector3::new(-5., 1.1, 0.), Vector3::unit_x()).is_none());
+        assert!(intersect_unit_cylinder(Vector3::new(-5., 0., 0.), -Vector3::unit_x()).is_none());
+    }
+
+    #[test]
+    fn cylinder_with_a_non_uniform_scale() {
+        // elliptic cylinder of half axes 2 along x & 1 along z, from y = -3 to 3, centered on (3, 0, 0)
+        let transform = Matrix4::from_translation(Vector3::new(3., 0., 0.))*Matrix4::from_nonuniform_scale(2., 3., 1.);
+        let intersect = |origin: Vector3<f32>, direction: Vector3<f32>| Ray::new(origin, direction).intersect_cylinder(2., 1., &transform);
+
+        let hit = intersect(Vector3::new(-7., 0.5, 0.), Vector3::unit_x());
+        assert_hit(hit, 8., Vector3::new(1., 0.5, 0.), -Vector3::unit_x());
+        let hit = intersect(Vector3::new(3.5, 10., 0.), -Vector3::unit_y());
+        assert_hit(hit, 7., Vector3::new(3.5, 3., 0.), Vector3::unit_y());
+
+        // at 45° on the ellipse the normal is (x/4, 0, z/1) and not the radial direction
+        let c = std::f32::consts::FRAC_1_SQRT_2;
+        let point = Vector3::new(3. + 2.*c, 1., c);
+        let normal = Vector3::new(0.5, 0., 1.).normalize();
+        assert_hit(intersect(point + normal*5., -normal), 5., point, normal);
+
+        // the distances are the ones of the world ray, even when its direction isn't normalized
+        let hit = intersect(Vector3::new(-7., 0.5, 0.), Vector3::unit_x()*2.);
+        assert_hit(hit, 4., Vector3::new(1., 0.5, 0.), -Vector3::unit_x());
+    }
+
+    #[test]
+    fn triangle_edges_and_back_face() {
+        let triangle = [Vector3::new(0., 0., 0.), Vector3::new(1., 0., 0.), Vector3::new(0., 1., 0.)];
+        let intersect = |x: f32, y: f32, z: f32, direction: Vector3<f32>| Ray::new(Vector3::new(x, y, z), direction).intersect_triangle(triangle);
+
+        assert_eq!(intersect(0.25, 0.25, 1., -Vector3::unit_z()), Some(1.));
+        // from behind
+        assert_eq!(intersect(0.25, 0.25, -2., Vector3::unit_z()), Some(2.));
+        // on the edges & a corner, then just outside
+        assert_eq!(intersect(0.5, 0.5, 1., -Vector3::unit_z()), Some(1.));
+        assert_eq!(intersect(0.5, 0., 1., -Vector3::unit_z()), Some(1.));
+        assert_eq!(intersect(0., 0., 1., -Vector3::unit_z()), Some(1.));
+        assert_eq!(intersect(0.5, 0.51, 1., -Vector3::unit_z()), None);
+        assert_eq!(intersect(-0.01, 0.5, 1., -Vector3::unit_z()), None);
+        // parallel to the triangle, and pointing away from it
+        assert_eq!(intersect(-1., 0.25, 0., Vector3::unit_x()), None);
+        assert_eq!(intersect(0.25, 0.25, 1., Vector3::unit_z()), None);
+    }
+
+    #[test]
+    fn aabb_slabs() {
+        let aabb = Aabb { min: Vector3::new(-1., -1., -1.), max: Vector3::new(1., 1., 1.) };
+        assert_eq!(Ray::new(Vector3::new(-5., 0., 0.), Vector3::unit_x()).intersect_aabb(&aabb), Some(4.));
+        assert_eq!(Ray::new(Vector3::new(0., 0., 0.), Vector3::unit_y()).intersect_aabb(&aabb), Some(0.));
+        assert_eq!(Ray::new(Vector3::new(-5., 2., 0.), Vector3::unit_x()).intersect_aabb(&aabb), None);
+        assert_eq!(Ray::new(Vector3::new(5., 0., 0.), Vector3::unit_x()).intersect_aabb(&aabb), None);
+        let diagonal = Ray::new(Vector3::new(-3., -3., 0.), Vector3::new(1., 1., 0.).normalize());
+        assert!((diagonal.intersect_aabb(&aabb).unwrap() - 2.*2f32.sqrt()).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,688 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::Add;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::bounds::Aabb;
+use crate::mesh::{generate_tangents, Mesh, TriangleMesh, Vertex};
+use crate::optimize::{get_weld_remap, optimize_vertex_fetch, weld_vertices};
+
+// vertices closer than this fraction of the diagonal of the mesh are at the same point
+const POSITION_EPSILON: f32 = 1e-6;
+
+// largest difference between the normals of the two sides of an edge that isn't a hard edge
+const NORMAL_EPSILON: f32 = 1e-4;
+
+// weight of the planes holding the boundaries (and the sharp edges when they are kept) during decimation, relative
+// to the planes of the faces
+const CONSTRAINT_WEIGHT: f64 = 100.;
+
+// What subdivision & decimation should keep. Sharp edges are the edges along which the normals are split, like the
+// rims of the caps of a cylinder
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Features {
+    // subdivision doesn't move the boundaries, decimation doesn't remove their vertices
+    pub keep_boundaries: bool,
+    // the sharp edges stay sharp when subdividing and keep their shape when decimating
+    pub keep_sharp_edges: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EdgeKind {
+    Smooth,
+    Sharp,
+    // the uvs are split along the edge but not the normals, only told apart when decimating
+    Seam,
+    // an edge with a single triangle, or more than two
+    Boundary,
+}
+
+// Point of each vertex, merging the vertices at the same position (split along the uv seams & hard edges) so
+// the triangles are connected through them, and the number of points
+fn get_points(vertices: &[Vertex]) -> (Vec<u32>, usize) {
+    let (points, remap) = get_weld_remap(vertices, get_position_epsilon(vertices), |_, _| true);
+    (remap, points.len())
+}
+
+fn get_position_epsilon(vertices: &[Vertex]) -> f32 {
+    let aabb = Aabb::from_vertices(vertices);
+    if aabb.is_empty() {0.} else {(aabb.max - aabb.min).magnitude()*POSITION_EPSILON}
+}
+
+// vertex of the triangle at the point
+fn get_corner(triangle: [u32; 3], points: &[u32], point: u32) -> u32 {
+    *triangle.iter().find(|&&index| points[index as usize] == point).unwrap()
+}
+
+fn get_edge_kind(vertices: &[Vertex], points: &[u32], edge: (u32, u32), triangles: &[[u32; 3]]) -> EdgeKind {
+    if triangles.len() != 2 {
+        return EdgeKind::Boundary;
+    }
+    let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() <= NORMAL_EPSILON);
+    let split = [edge.0, edge.1].iter().any(|&point| {
+        let normal = |triangle: [u32; 3]| vertices[get_corner(triangle, points, point) as usize].get_normal();
+        !close(normal(triangles[0]), normal(triangles[1]))
+    });
+    if split {EdgeKind::Sharp} else {EdgeKind::Smooth}
+}
+
+// Loop subdivision: each level splits every triangle in 4 and moves the points towards the limit surface, which
+// is smooth except along the boundaries & kept sharp edges where it follows a cubic B-spline (or the original
+// polyline for the kept boundaries). The uvs are interpolated linearly and the normals computed again from the
+// faces, split along the sharp edges that are kept
+pub fn subdivide(mesh: &dyn Mesh, nb_levels: usize, features: Features) -> TriangleMesh {
+    let mut vertices = mesh.get_vertices().clone();
+    let mut indices = mesh.get_indices().clone();
+    for _ in 0..nb_levels {
+        (vertices, indices) = subdivide_once(&vertices, &indices, features);
+    }
+
+    let positions: Vec<[f32; 3]> = vertices.iter().map(|vertex| vertex.get_position()).collect();
+    let normals: Vec<[f32; 3]> = vertices.iter().map(|vertex| vertex.get_normal()).collect();
+    let uvs: Vec<[f32; 2]> = vertices.iter().map(|vertex| vertex.get_uv()).collect();
+    let tangents = generate_tangents(&positions, &normals, &uvs, &indices);
+    let vertices: Vec<Vertex> = (0..vertices.len()).map(|i| Vertex::new(positions[i], normals[i], uvs[i], tangents[i])).collect();
+    let (vertices, indices) = optimize_vertex_fetch(&vertices, &indices);
+    TriangleMesh::new(vertices, indices)
+}
+
+fn subdivide_once(vertices: &[Vertex], indices: &[[u32; 3]], features: Features) -> (Vec<Vertex>, Vec<[u32; 3]>) {
+    let (points, nb_points) = get_points(vertices);
+    let mut positions = vec![Vector3::new(0., 0., 0.); nb_points];
+    for (vertex, &point) in vertices.iter().zip(&points) {
+        positions[point as usize] = Vector3::from(vertex.get_position());
+    }
+    // the triangles with two corners at the same point have no area and no edges to split
+    let indices: Vec<[u32; 3]> = indices.iter().copied().filter(|triangle| {
+        let [a, b, c] = triangle.map(|index| points[index as usize]);
+        a != b && b != c && c != a
+    }).collect();
+
+    // triangles around each edge between two points, smallest point first
+    let mut edge_triangles: HashMap<(u32, u32), Vec<[u32; 3]>> = HashMap::new();
+    for &triangle in &indices {
+        for k in 0..3 {
+            let (a, b) = (points[triangle[k] as usize], points[triangle[(k + 1)%3] as usize]);
+            edge_triangles.entry((a.min(b), a.max(b))).or_default().push(triangle);
+        }
+    }
+    // get_edge_kind never tells the seams apart, they are smooth here
+    let crease = |kind: EdgeKind| kind == EdgeKind::Boundary || (kind == EdgeKind::Sharp && features.keep_sharp_edges);
+    let mut edge_kinds: HashMap<(u32, u32), EdgeKind> = HashMap::new();
+    let mut neighbors = vec![Vec::new(); nb_points];
+    let mut crease_neighbors = vec![Vec::new(); nb_points];
+    let mut on_boundary = vec![false; nb_points];
+    for (&edge, triangles) in &edge_triangles {
+        let kind = get_edge_kind(vertices, &points, edge, triangles);
+        let (a, b) = (edge.0 as usize, edge.1 as usize);
+        neighbors[a].push(b);
+        neighbors[b].push(a);
+        if crease(kind) {
+            crease_neighbors[a].push(b);
+            crease_neighbors[b].push(a);
+        }
+        if kind == EdgeKind::Boundary {
+            on_boundary[a] = true;
+            on_boundary[b] = true;
+        }
+        edge_kinds.insert(edge, kind);
+    }
+    let is_crease = |edge: &(u32, u32)| crease(edge_kinds[edge]);
+
+    // new positions of the points
+    let even: Vec<Vector3<f32>> = (0..nb_points).map(|point| {
+        let position = positions[point];
+        if features.keep_boundaries && on_boundary[point] {
+            return position;
+        }
+        match crease_neighbors[point][..] {
+            // smooth or dart point, with the weights of Warren
+            [] | [_] => {
+                let n = neighbors[point].len();
+                if n == 0 {
+                    return position;
+                }
+                let beta = if n == 3 {3./16.} else {3./(8.*n as f32)};
+                let sum = neighbors[point].iter().fold(Vector3::new(0., 0., 0.), |sum, &neighbor| sum + positions[neighbor]);
+                position*(1. - n as f32*beta) + sum*beta
+            },
+            [a, b] => position*0.75 + (positions[a] + positions[b])*0.125,
+            // corner
+            _ => position,
+        }
+    }).collect();
+
+    // points added on the edges
+    let odd: HashMap<(u32, u32), Vector3<f32>> = edge_triangles.iter().map(|(edge, triangles)| {
+        let (a, b) = (positions[edge.0 as usize], positions[edge.1 as usize]);
+        if is_crease(edge) {
+            return (*edge, (a + b)/2.);
+        }
+        let opposite = |triangle: &[u32; 3]| {
+            let point = triangle.iter().map(|&index| points[index as usize]).find(|point| *point != edge.0 && *point != edge.1).unwrap();
+            positions[point as usize]
+        };
+        (*edge, (a + b)*0.375 + (opposite(&triangles[0]) + opposite(&triangles[1]))*0.125)
+    }).collect();
+
+    // the vertices keep their uvs and split the points as before, the normals are only interpolated to tell the
+    // sides of the sharp edges apart
+    let mut new_vertices: Vec<Vertex> = vertices.iter().zip(&points).map(|(vertex, &point)| {
+        let position = even[point as usize];
+        Vertex::new([position.x, position.y, position.z], vertex.get_normal(), vertex.get_uv(), vertex.get_tangent())
+    }).collect();
+    let mut new_points: Vec<u32> = points.clone();
+    let mut edge_points: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut edge_vertices: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut new_indices = Vec::with_capacity(4*indices.len());
+    for triangle in &indices {
+        let [ab, bc, ca] = [0, 1, 2].map(|k| {
+            let (a, b) = (triangle[k], triangle[(k + 1)%3]);
+            *edge_vertices.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let (va, vb) = (&vertices[a as usize], &vertices[b as usize]);
+                let (pa, pb) = (points[a as usize], points[b as usize]);
+                let edge = (pa.min(pb), pa.max(pb));
+                let position = odd[&edge];
+                let normal = (Vector3::from(va.get_normal()) + Vector3::from(vb.get_normal())).normalize();
+                let [ua, ub] = [va.get_uv(), vb.get_uv()];
+                new_vertices.push(Vertex::new(
+                    [position.x, position.y, position.z],
+                    [normal.x, normal.y, normal.z],
+                    [(ua[0] + ub[0])/2., (ua[1] + ub[1])/2.],
+                    va.get_tangent(),
+                ));
+                let nb_edge_points = edge_points.len();
+                new_points.push(*edge_points.entry(edge).or_insert((nb_points + nb_edge_points).try_into().unwrap()));
+                (new_vertices.len() - 1).try_into().unwrap()
+            })
+        });
+        let [a, b, c] = *triangle;
+        new_indices.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+    }
+
+    // smooth normals of the faces around each point, separately for each side of the sharp edges
+    let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() <= NORMAL_EPSILON);
+    let mut groups: HashMap<u32, Vec<([f32; 3], usize)>> = HashMap::new();
+    let mut vertex_groups = Vec::with_capacity(new_vertices.len());
+    let mut nb_groups = 0;
+    for (vertex, point) in new_vertices.iter().zip(&new_points) {
+        let point_groups = groups.entry(*point).or_default();
+        let normal = vertex.get_normal();
+        let existing = point_groups.iter().find(|(group_normal, _)| !features.keep_sharp_edges || close(*group_normal, normal));
+        let group = existing.map(|(_, group)| *group).unwrap_or_else(|| {
+            point_groups.push((normal, nb_groups));
+            nb_groups += 1;
+            nb_groups - 1
+        });
+        vertex_groups.push(group);
+    }
+    let mut group_normals = vec![Vector3::new(0., 0., 0.); nb_groups];
+    for triangle in &new_indices {
+        let [a, b, c] = triangle.map(|index| Vector3::from(new_vertices[index as usize].get_position()));
+        // weighted by the area
+        let normal = (b - a).cross(c - a);
+        for &index in triangle {
+            group_normals[vertex_groups[index as usize]] += normal;
+        }
+    }
+    for (vertex, &group) in new_vertices.iter_mut().zip(&vertex_groups) {
+        let normal = group_normals[group];
+        if normal.magnitude2() > 0. {
+            let normal = normal.normalize();
+            *vertex = Vertex::new(vertex.get_position(), [normal.x, normal.y, normal.z], vertex.get_uv(), vertex.get_tangent());
+        }
+    }
+    (new_vertices, new_indices)
+}
+
+// Error quadric (Garland & Heckbert): sum of the squared distances to a set of weighted planes, as the upper
+// half of a symmetric 4x4 matrix
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(normal: Vector3<f64>, point: Vector3<f64>, weight: f64) -> Self {
+        let (a, b, c, d) = (normal.x, normal.y, normal.z, -normal.dot(point));
+        Self([a*a, a*b, a*c, a*d, b*b, b*c, b*d, c*c, c*d, d*d].map(|value| value*weight))
+    }
+
+    fn get_error(&self, point: Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (point.x, point.y, point.z);
+        x*x*q[0] + 2.*x*y*q[1] + 2.*x*z*q[2] + 2.*x*q[3]
+            + y*y*q[4] + 2.*y*z*q[5] + 2.*y*q[6]
+            + z*z*q[7] + 2.*z*q[8]
+            + q[9]
+    }
+}
+
+impl Add for Quadric {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let mut sum = self.0;
+        for (value, other) in sum.iter_mut().zip(other.0) {
+            *value += other;
+        }
+        Self(sum)
+    }
+}
+
+// Moving the point from onto the point to, removing the triangles between them, ordered by increasing cost in
+// a BinaryHeap
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    // versions of the two points when the cost was computed
+    versions: [u32; 2],
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+// Quadric error edge collapse down to about nb_triangles triangles (fewer are kept when the mesh can't be
+// simplified further without folding it). The edges are collapsed onto one of their ends so the vertices left
+// are original ones with their attributes, and the points along the uv seams, hard edges & boundaries only move
+// along them
+pub fn decimate(mesh: &dyn Mesh, nb_triangles: usize, features: Features) -> TriangleMesh {
+    let (vertices, indices) = weld_vertices(mesh.get_vertices(), mesh.get_indices(), get_position_epsilon(mesh.get_vertices()));
+    let mut decimation = Decimation::new(&vertices, &indices, features);
+    decimation.run(nb_triangles);
+    let (vertices, indices) = optimize_vertex_fetch(&vertices, &decimation.get_triangles());
+    TriangleMesh::new(vertices, indices)
+}
+
+// Levels of detail of any mesh: the mesh itself then nb_levels meshes with each half the triangles of the
+// previous one
+pub fn get_decimation_chain(mesh: &dyn Mesh, nb_levels: usize, features: Features) -> Vec<TriangleMesh> {
+    let mut chain = vec![TriangleMesh::new(mesh.get_vertices().clone(), mesh.get_indices().clone())];
+    for _ in 0..nb_levels {
+        let previous = chain.last().unwrap();
+        let level = decimate(previous, previous.get_indices().len()/2, features);
+        chain.push(level);
+    }
+    chain
+}
+
+struct Decimation<'a> {
+    vertices: &'a [Vertex],
+    features: Features,
+    // point of each vertex
+    points: Vec<u32>,
+    positions: Vec<Vector3<f64>>,
+    triangles: Vec<[u32; 3]>,
+    removed: Vec<bool>,
+    // triangles around each point, the removed ones are dropped from the points the collapses move onto
+    point_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    // incremented when the triangles around the point change, to skip the outdated collapses
+    versions: Vec<u32>,
+}
+
+impl<'a> Decimation<'a> {
+    fn new(vertices: &'a [Vertex], indices: &[[u32; 3]], features: Features) -> Self {
+        let (points, nb_points) = get_points(vertices);
+        let mut positions = vec![Vector3::new(0., 0., 0.); nb_points];
+        for (vertex, &point) in vertices.iter().zip(&points) {
+            positions[point as usize] = Vector3::from(vertex.get_position()).cast().unwrap();
+        }
+        let triangles: Vec<[u32; 3]> = indices.iter().copied().filter(|triangle| {
+            let [a, b, c] = triangle.map(|index| points[index as usize]);
+            a != b && b != c && c != a
+        }).collect();
+        let mut point_triangles = vec![Vec::new(); nb_points];
+        for (t, triangle) in triangles.iter().enumerate() {
+            for &index in triangle {
+                point_triangles[points[index as usize] as usize].push(t);
+            }
+        }
+
+        let mut decimation = Self {
+            vertices,
+            features,
+            points,
+            positions,
+            removed: vec![false; triangles.len()],
+            triangles,
+            point_triangles,
+            quadrics: vec![Quadric::default(); nb_points],
+            versions: vec![0; nb_points],
+        };
+        decimation.init_quadrics();
+        decimation
+    }
+
+    // planes of the faces weighted by their area, and planes perpendicular to the faces along the boundaries
+    // & sharp edges to keep
+    fn init_quadrics(&mut self) {
+        for t in 0..self.triangles.len() {
+            let corners = self.get_triangle_points(t);
+            let [a, b, c] = corners.map(|point| self.positions[point as usize]);
+            let normal = (b - a).cross(c - a);
+            let area = normal.magnitude()/2.;
+            if area <= 0. {
+                continue;
+            }
+            let normal = normal.normalize();
+            let quadric = Quadric::from_plane(normal, a, area);
+            for point in corners {
+                self.quadrics[point as usize] = self.quadrics[point as usize] + quadric;
+            }
+
+            for k in 0..3 {
+                let (p, q) = (corners[k], corners[(k + 1)%3]);
+                let kind = self.get_edge_kind(p, q);
+                if kind == EdgeKind::Boundary || (kind == EdgeKind::Sharp && self.features.keep_sharp_edges) {
+                    let edge = self.positions[q as usize] - self.positions[p as usize];
+                    let constraint = Quadric::from_plane(edge.cross(normal).normalize(), self.positions[p as usize], CONSTRAINT_WEIGHT*edge.magnitude2());
+                    for point in [p, q] {
+                        self.quadrics[point as usize] = self.quadrics[point as usize] + constraint;
+                    }
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, nb_triangles: usize) {
+        let mut heap = BinaryHeap::new();
+        for point in 0..self.positions.len() {
+            self.push_collapses(&mut heap, point as u32);
+        }
+        let mut nb_left = self.triangles.len();
+        while nb_left > nb_triangles {
+            let Some(collapse) = heap.pop() else {
+                break;
+            };
+            let (from, to) = (collapse.from, collapse.to);
+            if collapse.versions != [self.versions[from as usize], self.versions[to as usize]] {
+                continue;
+            }
+            let Some(remap) = self.get_collapse(from, to) else {
+                continue;
+            };
+            nb_left -= self.collapse(from, to, &remap);
+
+            // only the quadric of the point moved onto has changed, the collapses left out around it are checked
+            // again when they come out of the heap
+            self.versions[to as usize] += 1;
+            self.push_collapses(&mut heap, to);
+        }
+    }
+
+    fn get_triangles(&self) -> Vec<[u32; 3]> {
+        self.triangles.iter().zip(&self.removed).filter(|(_, &removed)| !removed).map(|(triangle, _)| *triangle).collect()
+    }
+
+    fn get_triangle_points(&self, t: usize) -> [u32; 3] {
+        self.triangles[t].map(|index| self.points[index as usize])
+    }
+
+    fn get_live_triangles(&self, point: u32) -> impl Iterator<Item = usize> + '_ {
+        self.point_triangles[point as usize].iter().copied().filter(|&t| !self.removed[t])
+    }
+
+    fn get_edge_triangles(&self, p: u32, q: u32) -> Vec<usize> {
+        self.get_live_triangles(p).filter(|&t| self.get_triangle_points(t).contains(&q)).collect()
+    }
+
+    fn get_neighbors(&self, point: u32) -> Vec<u32> {
+        let mut neighbors: Vec<u32> = self.get_live_triangles(point)
+            .flat_map(|t| self.get_triangle_points(t))
+            .filter(|&neighbor| neighbor != point)
+            .collect();
+        neighbors.sort();
+        neighbors.dedup();
+        neighbors
+    }
+
+    fn get_edge_kind(&self, p: u32, q: u32) -> EdgeKind {
+        let triangles: Vec<[u32; 3]> = self.get_edge_triangles(p, q).iter().map(|&t| self.triangles[t]).collect();
+        if triangles.len() != 2 {
+            return EdgeKind::Boundary;
+        }
+        // uv seams constrain the collapses as much as hard edges
+        let split = [p, q].iter().any(|&point| {
+            get_corner(triangles[0], &self.points, point) != get_corner(triangles[1], &self.points, point)
+        });
+        if !split {
+            EdgeKind::Smooth
+        } else if get_edge_kind(self.vertices, &self.points, (p, q), &triangles) == EdgeKind::Sharp {
+            EdgeKind::Sharp
+        } else {
+            EdgeKind::Seam
+        }
+    }
+
+    // the collapses are only checked when they come out of the heap, most of them never do
+    fn push_collapses(&self, heap: &mut BinaryHeap<Collapse>, point: u32) {
+        for neighbor in self.get_neighbors(point) {
+            for (from, to) in [(point, neighbor), (neighbor, point)] {
+                let quadric = self.quadrics[from as usize] + self.quadrics[to as usize];
+                heap.push(Collapse {
+                    cost: quadric.get_error(self.positions[to as usize]).max(0.),
+                    from,
+                    to,
+                    versions: [self.versions[from as usize], self.versions[to as usize]],
+                });
+            }
+        }
+    }
+
+    // Vertex each vertex at from becomes when moving the point from onto the point to, if the mesh stays
+    // manifold, doesn't fold and keeps its features & attributes
+    fn get_collapse(&self, from: u32, to: u32) -> Option<HashMap<u32, u32>> {
+        let edge_triangles = self.get_edge_triangles(from, to);
+        if edge_triangles.is_empty() {
+            return None;
+        }
+
+        // a point on the seams, hard edges or boundaries only slides along them, and doesn't move at their
+        // corners
+        let neighbors = self.get_neighbors(from);
+        let feature_edges: Vec<(u32, EdgeKind)> = neighbors.iter()
+            .map(|&neighbor| (neighbor, self.get_edge_kind(from, neighbor)))
+            .filter(|(_, kind)| *kind != EdgeKind::Smooth)
+            .collect();
+        if !feature_edges.is_empty() {
+            let on_boundary = feature_edges.iter().any(|(_, kind)| *kind == EdgeKind::Boundary);
+            let along = feature_edges.iter().any(|(neighbor, _)| *neighbor == to);
+            if (self.features.keep_boundaries && on_boundary) || feature_edges.len() != 2 || !along {
+                return None;
+            }
+        }
+
+        // the points next to both must be the third points of the triangles removed, or the collapse would pinch
+        // the mesh
+        let to_neighbors = self.get_neighbors(to);
+        let nb_common = neighbors.iter().filter(|neighbor| to_neighbors.binary_search(neighbor).is_ok()).count();
+        if nb_common != edge_triangles.len() {
+            return None;
+        }
+
+        // the vertices at from take the ones at to of the same triangles
+        let mut remap = HashMap::new();
+        for &t in &edge_triangles {
+            let (from_vertex, to_vertex) = (get_corner(self.triangles[t], &self.points, from), get_corner(self.triangles[t], &self.points, to));
+            if *remap.entry(from_vertex).or_insert(to_vertex) != to_vertex {
+                return None;
+            }
+        }
+        for t in self.get_live_triangles(from).filter(|t| !edge_triangles.contains(t)) {
+            if !remap.contains_key(&get_corner(self.triangles[t], &self.points, from)) {
+                return None;
+            }
+            // the triangles left must not flip or become flat
+            let corners = self.get_triangle_points(t);
+            let [a, b, c] = corners.map(|point| self.positions[point as usize]);
+            let [na, nb, nc] = corners.map(|point| self.positions[if point == from {to} else {point} as usize]);
+            if (b - a).cross(c - a).dot((nb - na).cross(nc - na)) <= 0. {
+                return None;
+            }
+        }
+        Some(remap)
+    }
+
+    // number of triangles removed
+    fn collapse(&mut self, from: u32, to: u32, remap: &HashMap<u32, u32>) -> usize {
+        let mut nb_removed = 0;
+        for t in self.point_triangles[from as usize].clone() {
+            if self.removed[t] {
+                continue;
+            }
+            if self.get_triangle_points(t).contains(&to) {
+                self.removed[t] = true;
+                nb_removed += 1;
+            } else {
+                for index in self.triangles[t].iter_mut() {
+                    if self.points[*index as usize] == from {
+                        *index = remap[index];
+                    }
+                }
+                self.point_triangles[to as usize].push(t);
+            }
+        }
+        self.point_triangles[from as usize].clear();
+        let removed = &self.removed;
+        self.point_triangles[to as usize].retain(|&t| !removed[t]);
+        self.quadrics[to as usize] = self.quadrics[to as usize] + self.quadrics[from as usize];
+        self.versions[from as usize] += 1;
+        nb_removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+    use crate::mass::mesh_mass_properties;
+    use crate::plane::Plane;
+    use crate::sphere::IcoSphere;
+    use crate::torus::Torus;
+    use crate::validate::validate_mesh;
+
+    const KEEP: Features = Features { keep_boundaries: true, keep_sharp_edges: true };
+
+    fn max_y(mesh: &dyn Mesh) -> f32 {
+        mesh.get_vertices().iter().fold(f32::MIN, |max_y, vertex| max_y.max(vertex.get_position()[1]))
+    }
+
+    // vertices on the border of a 2 x 2 plane
+    fn nb_border_vertices(mesh: &dyn Mesh) -> usize {
+        mesh.get_vertices().iter().filter(|vertex| {
+            let [x, _, z] = vertex.get_position();
+            (x.abs() - 1.).abs() < 1e-5 || (z.abs() - 1.).abs() < 1e-5
+        }).count()
+    }
+
+    #[test]
+    fn subdivided_sphere_stays_round() {
+        let sphere = IcoSphere::new(0, 1.);
+        for nb_levels in 1..4 {
+            let subdivided = subdivide(&sphere, nb_levels, Features::default());
+            assert_eq!(subdivided.get_indices().len(), sphere.get_indices().len()*4usize.pow(nb_levels as u32));
+            assert!(validate_mesh(&subdivided).is_watertight());
+            let radii: Vec<f32> = subdivided.get_vertices().iter()
+                .map(|vertex| Vector3::from(vertex.get_position()).magnitude())
+                .collect();
+            let spread = radii.iter().fold(f32::MIN, |a, &b| a.max(b)) - radii.iter().fold(f32::MAX, |a, &b| a.min(b));
+            assert!(spread < 0.03, "{} levels: radius spread {}", nb_levels, spread);
+        }
+    }
+
+    #[test]
+    fn subdivision_keeps_the_sharp_rims() {
+        let cylinder = Cylinder::new(16, 1., 0.5);
+        let subdivided = subdivide(&cylinder, 2, KEEP);
+        assert!(validate_mesh(&subdivided).is_watertight());
+        assert!((max_y(&subdivided) - 0.5).abs() < 1e-5);
+        for vertex in subdivided.get_vertices() {
+            if vertex.get_normal()[1] > 0.9999 {
+                assert!((vertex.get_position()[1] - 0.5).abs() < 1e-5);
+            }
+        }
+
+        // without the sharp edges the rims are rounded off
+        let smoothed = subdivide(&cylinder, 2, Features::default());
+        assert!(validate_mesh(&smoothed).is_watertight());
+        let rim_y = smoothed.get_vertices().iter()
+            .filter(|vertex| {
+                let [x, _, z] = vertex.get_position();
+                x*x + z*z > 0.2
+            })
+            .fold(f32::MIN, |max_y, vertex| max_y.max(vertex.get_position()[1]));
+        assert!(rim_y < 0.49);
+    }
+
+    #[test]
+    fn subdivision_keeps_the_boundaries() {
+        let plane = Plane::new(2., 2., 4, 4);
+        let subdivided = subdivide(&plane, 2, KEEP);
+        assert_eq!(nb_border_vertices(&subdivided), 4*16);
+    }
+
+    #[test]
+    fn decimated_sphere_keeps_its_volume() {
+        let sphere = IcoSphere::new(4, 1.);
+        let decimated = decimate(&sphere, 200, Features::default());
+        assert!(decimated.get_indices().len() <= 200 && decimated.get_indices().len() > 150);
+        assert!(validate_mesh(&decimated).is_watertight());
+        let (before, after) = (mesh_mass_properties(&sphere).volume, mesh_mass_properties(&decimated).volume);
+        assert!((after - before).abs()/before < 0.1, "volume {} -> {}", before, after);
+    }
+
+    #[test]
+    fn decimation_keeps_the_seams() {
+        let torus = Torus::new(64, 32, 1., 0.3);
+        let decimated = decimate(&torus, 500, Features::default());
+        assert!(decimated.get_indices().len() <= 500);
+        assert!(validate_mesh(&decimated).is_watertight());
+        // the collapses only move vertices onto existing ones, so every (position, uv) pair is an original one
+        for vertex in decimated.get_vertices() {
+            assert!(torus.get_vertices().iter()
+                .any(|other| other.get_position() == vertex.get_position() && other.get_uv() == vertex.get_uv()));
+        }
+    }
+
+    #[test]
+    fn decimation_keeps_the_sharp_rims_and_boundaries() {
+        let cylinder = subdivide(&Cylinder::new(64, 1., 0.5), 1, KEEP);
+        let decimated = decimate(&cylinder, 300, KEEP);
+        assert!(validate_mesh(&decimated).is_watertight());
+        assert!((max_y(&decimated) - 0.5).abs() < 1e-5);
+
+        let plane = Plane::new(2., 2., 16, 16);
+        assert_eq!(nb_border_vertices(&decimate(&plane, 20, KEEP)), 64);
+        assert!(decimate(&plane, 20, Features::default()).get_indices().len() <= 20);
+    }
+
+    #[test]
+    fn decimation_chain_halves_the_triangles() {
+        let sphere = IcoSphere::new(4, 1.);
+        let counts: Vec<usize> = get_decimation_chain(&sphere, 3, Features::default()).iter()
+            .map(|level| level.get_indices().len())
+            .collect();
+        assert_eq!(counts[0], sphere.get_indices().len());
+        for pair in counts.windows(2) {
+            assert!(pair[1] <= pair[0]/2);
+        }
+    }
+}
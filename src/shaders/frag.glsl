@@ -1,6 +1,7 @@
 #version 330 core
 in vec3 fragPos;
 in vec3 normalVect;
+in vec2 texCoord;
 out vec4 FragColor;
 
 uniform float u_time;
@@ -1,6 +1,8 @@
 #version 330 core
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoord;
+layout (location = 3) in vec4 aTangent;
 
 uniform mat4 view;
 uniform mat4 model;
@@ -8,10 +10,15 @@ uniform mat4 proj;
 
 out vec3 fragPos;
 out vec3 normalVect;
+out vec2 texCoord;
+out vec4 tangentVect;
 
 void main()
 {
     gl_Position = proj*view*model*vec4(aPos, 1.0);
     fragPos = vec3(model * vec4(aPos,1.0));
-    normalVect = aNormal;
+    // the normals need the inverse transpose so they stay perpendicular under a non uniform scale
+    normalVect = mat3(transpose(inverse(model)))*aNormal;
+    texCoord = aTexCoord;
+    tangentVect = vec4(mat3(model)*aTangent.xyz, aTangent.w);
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::ops::Deref;
+
+use crate::cylinder::{Cylinder, Ring};
+use crate::mesh::{triangle, TriangleMesh, Vertex};
+
+// Sphere made of latitude rings, same uv layout as the cylinder side wall
+pub struct UvSphere(TriangleMesh);
+
+impl Deref for UvSphere {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl UvSphere {
+    pub fn new(nb_slices: usize, nb_stacks: usize, radius: f32) -> Self {
+        assert!(nb_stacks > 1, "a uv sphere needs at least two stacks");
+
+        let mut vertices = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        // rings from the north pole to the south pole, which both collapse to a single vertex
+        let rings: Vec<Ring> = (0..=nb_stacks).map(|k| {
+            let v = 1. - (k as f32)/(nb_stacks as f32);
+            let latitude = PI*(v - 0.5);
+            Ring {
+                radius: if k == 0 || k == nb_stacks {0.} else {radius*latitude.cos()},
+                h: radius*latitude.sin(),
+                normal: [latitude.cos(), latitude.sin()],
+                v,
+            }
+        }).collect();
+        Cylinder::push_rings(&mut vertices, &mut indices, &rings, nb_slices, 0., 2.*PI, false);
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+}
+
+// Sphere made of evenly sized triangles, by subdividing an icosahedron
+pub struct IcoSphere(TriangleMesh);
+
+impl Deref for IcoSphere {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl IcoSphere {
+    pub fn new(nb_subdivisions: usize, radius: f32) -> Self {
+        // unit icosahedron, counter-clockwise faces seen from outside
+        let t = (1. + 5f32.sqrt())/2.;
+        let mut points: Vec<[f32; 3]> = [
+            [-1., t, 0.], [1., t, 0.], [-1., -t, 0.], [1., -t, 0.],
+            [0., -1., t], [0., 1., t], [0., -1., -t], [0., 1., -t],
+            [t, 0., -1.], [t, 0., 1.], [-t, 0., -1.], [-t, 0., 1.],
+        ].iter().map(|p| Self::normalize(*p)).collect();
+        let mut faces: Vec<[usize; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        // every subdivision splits each triangle in 4, the midpoints shared by two triangles are only created once
+        for _ in 0..nb_subdivisions {
+            let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut midpoint = |a: usize, b: usize, points: &mut Vec<[f32; 3]>| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let (pa, pb) = (points[a], points[b]);
+                    points.push(Self::normalize([pa[0] + pb[0], pa[1] + pb[1], pa[2] + pb[2]]));
+                    points.len() - 1
+                })
+            };
+            let mut new_faces = Vec::with_capacity(4*faces.len());
+            for [a, b, c] in faces {
+                let ab = midpoint(a, b, &mut points);
+                let bc = midpoint(b, c, &mut points);
+                let ca = midpoint(c, a, &mut points);
+                new_faces.extend([[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+            }
+            faces = new_faces;
+        }
+
+        let mut vertices: Vec<Vertex> = points.iter().map(|p| Self::vertex(*p, radius, None)).collect();
+        let mut indices = Vec::with_capacity(faces.len());
+
+        // spherical uv mapping, same layout as the uv sphere. Triangles crossing the seam get their own copy
+        // of the vertices on the u = 0 side with u = 1, and the poles take the u of the middle of their triangle
+        let is_pole = |p: [f32; 3]| p[0] == 0. && p[2] == 0.;
+        for face in faces {
+            let us: Vec<f32> = face.iter().map(|&i| vertices[i].get_uv()[0]).collect();
+            let seam_us = (0..3).filter(|&k| !is_pole(points[face[k]])).map(|k| us[k]);
+            let crosses_seam = seam_us.clone().fold(f32::MIN, f32::max) - seam_us.fold(f32::MAX, f32::min) > 0.5;
+            let points_indices = face;
+            let mut face = face;
+            for k in 0..3 {
+                if crosses_seam && us[k] < 0.5 && !is_pole(points[face[k]]) {
+                    vertices.push(Self::vertex(points[points_indices[k]], radius, Some(us[k] + 1.)));
+                    face[k] = vertices.len() - 1;
+                }
+            }
+            for k in 0..3 {
+                let p = points[points_indices[k]];
+                if is_pole(p) {
+                    let others: Vec<f32> = (0..3).filter(|&o| o != k).map(|o| vertices[face[o]].get_uv()[0]).collect();
+                    vertices.push(Self::vertex(p, radius, Some((others[0] + others[1])/2.)));
+                    face[k] = vertices.len() - 1;
+                }
+            }
+            indices.push(triangle(face[0], face[1], face[2]));
+        }
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+
+    fn normalize(p: [f32; 3]) -> [f32; 3] {
+        let length = (p[0]*p[0] + p[1]*p[1] + p[2]*p[2]).sqrt();
+        [p[0]/length, p[1]/length, p[2]/length]
+    }
+
+    // vertex at the unit direction n, the u coordinate can be overridden for the seam and the poles
+    fn vertex(n: [f32; 3], radius: f32, u: Option<f32>) -> Vertex {
+        let angle = n[2].atan2(n[0]).rem_euclid(2.*PI);
+        let u = u.unwrap_or(1. - angle/(2.*PI));
+        let v = 0.5 + n[1].clamp(-1., 1.).asin()/PI;
+        // the tangent follows u, which also gives the poles the direction of their triangle
+        let tangent_angle = 2.*PI*(1. - u);
+        let tangent = [tangent_angle.sin(), 0., -tangent_angle.cos(), 1.];
+        Vertex::new([n[0]*radius, n[1]*radius, n[2]*radius], n, [u, v], tangent)
+    }
+}
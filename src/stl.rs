@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::mesh::Mesh;
+
+pub enum StlFormat {
+    Ascii,
+    Binary,
+}
+
+// Unit the mesh dimensions are given in. STL files have no unit and are read as millimeters
+// by slicers, so the positions are scaled to millimeters when written
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Millimeter,
+    Inch,
+}
+
+impl Unit {
+    pub fn to_millimeters(self) -> f32 {
+        match self {
+            Unit::Millimeter => 1.,
+            Unit::Inch => 25.4,
+        }
+    }
+}
+
+pub fn export_stl(mesh: &dyn Mesh, path: &Path, format: StlFormat, unit: Unit) -> Result<()> {
+    let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("mesh");
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        StlFormat::Ascii => write_ascii_stl(&mut writer, mesh, name, unit)?,
+        StlFormat::Binary => write_binary_stl(&mut writer, mesh, name, unit)?,
+    }
+    writer.flush()
+}
+
+// (normal, vertices) of every triangle, the normal follows the counter-clockwise winding
+fn facets(mesh: &dyn Mesh, unit: Unit) -> impl Iterator<Item = (Vector3<f32>, [Vector3<f32>; 3])> + '_ {
+    let scale = unit.to_millimeters();
+    mesh.get_indices().iter().map(move |triangle| {
+        let [a, b, c] = triangle.map(|index| Vector3::from(mesh.get_vertices()[index as usize].get_position()) * scale);
+        let normal = (b - a).cross(c - a);
+        // degenerate triangles get a null normal, which readers recompute
+        let normal = if normal.magnitude2() > 0. {normal.normalize()} else {normal};
+        (normal, [a, b, c])
+    })
+}
+
+pub fn write_ascii_stl<W: Write>(writer: &mut W, mesh: &dyn Mesh, name: &str, unit: Unit) -> Result<()> {
+    writeln!(writer, "solid {}", name)?;
+    for (normal, triangle) in facets(mesh, unit) {
+        writeln!(writer, "  facet normal {} {} {}", normal.x, normal.y, normal.z)?;
+        writeln!(writer, "    outer loop")?;
+        for vertex in triangle {
+            writeln!(writer, "      vertex {} {} {}", vertex.x, vertex.y, vertex.z)?;
+        }
+        writeln!(writer, "    endloop")?;
+        writeln!(writer, "  endfacet")?;
+    }
+    writeln!(writer, "endsolid {}", name)
+}
+
+// 80 bytes header, triangle count then 50 bytes per triangle, all little endian
+pub fn write_binary_stl<W: Write>(writer: &mut W, mesh: &dyn Mesh, name: &str, unit: Unit) -> Result<()> {
+    // the header must not start with "solid" or readers take the file for an ascii one
+    let mut header = [0u8; 80];
+    let description = format!("binary STL {}", name);
+    let length = description.len().min(80);
+    header[..length].copy_from_slice(&description.as_bytes()[..length]);
+    writer.write_all(&header)?;
+
+    let nb_triangles: u32 = mesh.get_indices().len().try_into().unwrap();
+    writer.write_all(&nb_triangles.to_le_bytes())?;
+    for (normal, triangle) in facets(mesh, unit) {
+        for vector in [normal, triangle[0], triangle[1], triangle[2]] {
+            for coordinate in [vector.x, vector.y, vector.z] {
+                writer.write_all(&coordinate.to_le_bytes())?;
+            }
+        }
+        // attribute byte count, unused
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn binary_layout() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let nb_triangles = cylinder.get_indices().len();
+        let mut file = Vec::new();
+        write_binary_stl(&mut file, &cylinder, "cylinder", Unit::Millimeter).unwrap();
+
+        assert_eq!(file.len(), 84 + 50*nb_triangles);
+        assert!(!file.starts_with(b"solid"));
+        assert_eq!(u32::from_le_bytes(file[80..84].try_into().unwrap()) as usize, nb_triangles);
+        // first vertex of the first triangle, after its normal
+        let first = cylinder.get_vertices()[cylinder.get_indices()[0][0] as usize].get_position();
+        assert_eq!([0, 1, 2].map(|i| read_f32(&file, 84 + 12 + 4*i)), first);
+    }
+
+    #[test]
+    fn ascii_facets() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let mut file = Vec::new();
+        write_ascii_stl(&mut file, &cylinder, "cylinder", Unit::Millimeter).unwrap();
+        let text = String::from_utf8(file).unwrap();
+
+        assert!(text.starts_with("solid cylinder\n"));
+        assert!(text.ends_with("endsolid cylinder\n"));
+        let nb_facets = text.lines().filter(|line| line.trim_start().starts_with("facet normal")).count();
+        assert_eq!(nb_facets, cylinder.get_indices().len());
+        assert_eq!(text.lines().filter(|line| line.trim_start().starts_with("vertex")).count(), 3*nb_facets);
+    }
+
+    #[test]
+    fn inches_are_scaled_to_millimeters() {
+        let cylinder = Cylinder::new(10, 1., 0.5);
+        let (mut millimeters, mut inches) = (Vec::new(), Vec::new());
+        write_binary_stl(&mut millimeters, &cylinder, "cylinder", Unit::Millimeter).unwrap();
+        write_binary_stl(&mut inches, &cylinder, "cylinder", Unit::Inch).unwrap();
+
+        for triangle in 0..cylinder.get_indices().len() {
+            let facet = 84 + 50*triangle;
+            // the normals are the same, the vertices are scaled
+            for offset in (facet..facet + 12).step_by(4) {
+                assert!((read_f32(&inches, offset) - read_f32(&millimeters, offset)).abs() < 1e-6);
+            }
+            for offset in (facet + 12..facet + 48).step_by(4) {
+                let expected = 25.4*read_f32(&millimeters, offset);
+                assert!((read_f32(&inches, offset) - expected).abs() <= 1e-5*expected.abs().max(1.));
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use std::f32::consts::PI;
+use std::ops::Deref;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::cylinder::Cylinder;
+use crate::mesh::{triangle, TriangleMesh, Vertex};
+
+// Curve followed by a swept tube. Each variant has one radius per point the curve goes through
+pub enum SweepPath {
+    // straight segments between the points
+    Polyline(Vec<Vector3<f32>>),
+    // cubic Bezier segments sharing their ends: anchor, control, control, anchor, control, control, anchor...
+    // (3n + 1 points, n + 1 anchors) sampled nb_samples times per segment
+    Bezier(Vec<Vector3<f32>>, usize),
+    // centripetal Catmull-Rom spline going through all the points, sampled nb_samples times per segment.
+    // Unlike the uniform one it never makes cusps or loops when the points are unevenly spaced
+    CatmullRom(Vec<Vector3<f32>>, usize),
+}
+
+impl SweepPath {
+    // points & radii along the curve, with the radii linearly interpolated along each segment
+    fn sample(&self, radii: &[f32]) -> (Vec<Vector3<f32>>, Vec<f32>) {
+        let mut points = Vec::new();
+        let mut sample_radii = Vec::new();
+        match self {
+            SweepPath::Polyline(path_points) => {
+                assert_eq!(radii.len(), path_points.len(), "a polyline needs one radius per point");
+                points.extend(path_points);
+                sample_radii.extend(radii);
+            },
+            SweepPath::Bezier(path_points, nb_samples) => {
+                assert!(path_points.len() >= 4 && (path_points.len() - 1)%3 == 0, "a Bezier path needs 3n + 1 points");
+                assert_eq!(radii.len(), (path_points.len() - 1)/3 + 1, "a Bezier path needs one radius per anchor");
+                let nb_samples = (*nb_samples).max(1);
+                for (k, segment) in path_points.windows(4).step_by(3).enumerate() {
+                    for i in 0..nb_samples {
+                        let t = (i as f32)/(nb_samples as f32);
+                        let s = 1. - t;
+                        points.push(segment[0]*s*s*s + segment[1]*3.*s*s*t + segment[2]*3.*s*t*t + segment[3]*t*t*t);
+                        sample_radii.push(radii[k] + (radii[k+1] - radii[k])*t);
+                    }
+                }
+                points.push(path_points[path_points.len()-1]);
+                sample_radii.push(radii[radii.len()-1]);
+            },
+            SweepPath::CatmullRom(path_points, nb_samples) => {
+                assert_eq!(radii.len(), path_points.len(), "a Catmull-Rom path needs one radius per point");
+                // the knots must be distinct so repeated points are skipped
+                let mut unique_points: Vec<Vector3<f32>> = Vec::new();
+                let mut unique_radii = Vec::new();
+                for (&point, &radius) in path_points.iter().zip(radii) {
+                    if unique_points.last() != Some(&point) {
+                        unique_points.push(point);
+                        unique_radii.push(radius);
+                    }
+                }
+                let n = unique_points.len();
+                assert!(n >= 2, "a Catmull-Rom path needs at least 2 distinct points");
+
+                // the ends are extended by mirroring their neighbor so that the curve reaches them
+                let mut controls = vec![unique_points[0]*2. - unique_points[1]];
+                controls.extend(&unique_points);
+                controls.push(unique_points[n-1]*2. - unique_points[n-2]);
+
+                let nb_samples = (*nb_samples).max(1);
+                for (k, p) in controls.windows(4).enumerate() {
+                    // knots spaced by the square root of the distance between the points
+                    let t0 = 0.;
+                    let t1 = t0 + (p[1] - p[0]).magnitude().sqrt();
+                    let t2 = t1 + (p[2] - p[1]).magnitude().sqrt();
+                    let t3 = t2 + (p[3] - p[2]).magnitude().sqrt();
+                    let lerp = |a: Vector3<f32>, b: Vector3<f32>, ta: f32, tb: f32, t: f32| a*((tb - t)/(tb - ta)) + b*((t - ta)/(tb - ta));
+                    for i in 0..nb_samples {
+                        let s = (i as f32)/(nb_samples as f32);
+                        let t = t1 + (t2 - t1)*s;
+                        // Barry & Goldman's pyramidal formulation
+                        let a1 = lerp(p[0], p[1], t0, t1, t);
+                        let a2 = lerp(p[1], p[2], t1, t2, t);
+                        let a3 = lerp(p[2], p[3], t2, t3, t);
+                        let b1 = lerp(a1, a2, t0, t2, t);
+                        let b2 = lerp(a2, a3, t1, t3, t);
+                        points.push(lerp(b1, b2, t1, t2, t));
+                        sample_radii.push(unique_radii[k] + (unique_radii[k+1] - unique_radii[k])*s);
+                    }
+                }
+                points.push(unique_points[n-1]);
+                sample_radii.push(unique_radii[n-1]);
+            },
+        }
+        (points, sample_radii)
+    }
+}
+
+// Tube of circular cross-section swept along a path, for cables, trajectories & streamlines
+pub struct Sweep(TriangleMesh);
+
+impl Deref for Sweep {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl Sweep {
+    // The cross-sections are oriented with rotation minimizing frames so the tube doesn't twist. u goes
+    // around the tube & v along it (0 at the start, 1 at the end). The ends are closed by flat caps if asked
+    pub fn new(path: &SweepPath, radii: &[f32], nb_slices: usize, caps: bool) -> Self {
+        let (mut points, mut sample_radii) = path.sample(radii);
+        // repeated points have no direction
+        let mut k = 1;
+        while k < points.len() {
+            if points[k] == points[k-1] {
+                points.remove(k);
+                sample_radii.remove(k);
+            } else {
+                k += 1;
+            }
+        }
+        assert!(points.len() >= 2, "a swept tube needs at least 2 distinct points");
+        let n = points.len();
+
+        // tangents bisect the directions of the segments on both sides, except at the ends
+        let directions: Vec<Vector3<f32>> = points.windows(2).map(|p| (p[1] - p[0]).normalize()).collect();
+        let tangents: Vec<Vector3<f32>> = (0..n).map(|k| {
+            let tangent = if k == 0 {
+                directions[0]
+            } else if k == n-1 {
+                directions[n-2]
+            } else {
+                directions[k-1] + directions[k]
+            };
+            // a U-turn has no bisector, the incoming direction is the best guess
+            if tangent.magnitude2() < 1e-12 {directions[k-1]} else {tangent.normalize()}
+        }).collect();
+
+        // distance along the path, for v & the slope of the radius
+        let mut lengths = vec![0.];
+        for k in 1..n {
+            lengths.push(lengths[k-1] + (points[k] - points[k-1]).magnitude());
+        }
+        let length = lengths[n-1];
+
+        let frames = Self::get_rotation_minimizing_frames(&points, &tangents);
+        let (cosines, sines) = Cylinder::get_unit_circle_vertices(nb_slices, 0., 2.*PI);
+
+        let mut vertices = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        // a ring of nb_slices + 1 vertices (seam duplicated) per point
+        for k in 0..n {
+            let (tangent, normal, binormal) = (tangents[k], frames[k], tangents[k].cross(frames[k]));
+            // a radius varying along the path tilts the surface normals toward the end getting thinner
+            let (previous, next) = (k.saturating_sub(1), (k+1).min(n-1));
+            let slope = (sample_radii[next] - sample_radii[previous])/(lengths[next] - lengths[previous]);
+            for j in 0..=nb_slices {
+                let direction = normal*cosines[j] + binormal*sines[j];
+                let current_point = points[k] + direction*sample_radii[k];
+                let current_normal = (direction - tangent*slope).normalize();
+                let current_tangent = binormal*cosines[j] - normal*sines[j];
+                vertices.push(Vertex::new(
+                    current_point.into(),
+                    current_normal.into(),
+                    [(j as f32)/(nb_slices as f32), lengths[k]/length],
+                    [current_tangent.x, current_tangent.y, current_tangent.z, 1.],
+                ));
+            }
+        }
+        for k in 0..n-1 {
+            for j in 0..nb_slices {
+                let a = k*(nb_slices + 1) + j;
+                let c = a + nb_slices + 1;
+                indices.push(triangle(a, a+1, c));
+                indices.push(triangle(a+1, c+1, c));
+            }
+        }
+
+        if caps {
+            for end in [false, true] {
+                let k = if end {n-1} else {0};
+                let sign = if end {1.} else {-1.};
+                let (normal, binormal) = (frames[k], tangents[k].cross(frames[k]));
+                let cap_normal: [f32; 3] = (tangents[k]*sign).into();
+                let cap_tangent = [normal.x, normal.y, normal.z, 1.];
+                let center_index = vertices.len();
+
+                // planar mapping like the cylinder caps, flipped on the start cap so it reads the right way from outside
+                vertices.push(Vertex::new(points[k].into(), cap_normal, [0.5, 0.5], cap_tangent));
+                for j in 0..nb_slices {
+                    let current_point = points[k] + (normal*cosines[j] + binormal*sines[j])*sample_radii[k];
+                    let current_uv = [0.5 + 0.5*cosines[j], 0.5 + sign*0.5*sines[j]];
+                    vertices.push(Vertex::new(current_point.into(), cap_normal, current_uv, cap_tangent));
+                }
+
+                for j in 0..nb_slices {
+                    let index = center_index + 1 + j;
+                    let next_index = center_index + 1 + (j+1)%nb_slices;
+                    if end {
+                        indices.push(triangle(center_index, index, next_index));
+                    } else {
+                        indices.push(triangle(center_index, next_index, index));
+                    }
+                }
+            }
+        }
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+
+    // Normal of the frame at each point, carried along the path by the double reflection method
+    // (Wang et al. 2008) which approximates parallel transport much better than projecting the previous normal
+    fn get_rotation_minimizing_frames(points: &[Vector3<f32>], tangents: &[Vector3<f32>]) -> Vec<Vector3<f32>> {
+        // any direction perpendicular to the first tangent, starting from the world axis the least aligned with it
+        let tangent = tangents[0];
+        let axis = if tangent.x.abs() < tangent.y.abs() && tangent.x.abs() < tangent.z.abs() {
+            Vector3::unit_x()
+        } else if tangent.y.abs() < tangent.z.abs() {
+            Vector3::unit_y()
+        } else {
+            Vector3::unit_z()
+        };
+        let mut frames = vec![(axis - tangent*axis.dot(tangent)).normalize()];
+
+        for k in 0..points.len()-1 {
+            let normal = frames[k];
+            // reflection across the bisector plane of the segment
+            let v1 = points[k+1] - points[k];
+            let c1 = v1.magnitude2();
+            let reflected_normal = normal - v1*(2./c1*v1.dot(normal));
+            let reflected_tangent = tangents[k] - v1*(2./c1*v1.dot(tangents[k]));
+            // second reflection bringing the reflected tangent onto the next tangent
+            let v2 = tangents[k+1] - reflected_tangent;
+            let c2 = v2.magnitude2();
+            let next_normal = if c2 < 1e-12 {reflected_normal} else {reflected_normal - v2*(2./c2*v2.dot(reflected_normal))};
+            // keeps the frame orthonormal despite the rounding errors piling up
+            let next_normal = next_normal - tangents[k+1]*next_normal.dot(tangents[k+1]);
+            frames.push(next_normal.normalize());
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Mesh;
+    use crate::validate::validate_mesh;
+
+    // offsets of the first vertex of every ring from its point, i.e. the frame normal times the radius
+    fn get_ring_offsets(sweep: &Sweep, points: &[Vector3<f32>], nb_slices: usize) -> Vec<Vector3<f32>> {
+        points.iter().enumerate()
+            .map(|(k, &point)| Vector3::from(sweep.get_vertices()[k*(nb_slices + 1)].get_position()) - point)
+            .collect()
+    }
+
+    #[test]
+    fn capped_sweeps_are_watertight() {
+        let points = vec![
+            Vector3::new(0., 0., 0.), Vector3::new(1., 0.5, 0.), Vector3::new(2., 0., 1.),
+            Vector3::new(2., 1., 2.), Vector3::new(0., 2., 2.),
+        ];
+        let radii = [0.1, 0.2, 0.15, 0.3, 0.1];
+        let bezier = vec![
+            Vector3::new(0., 0., 0.), Vector3::new(1., 1., 0.), Vector3::new(2., -1., 1.), Vector3::new(3., 0., 1.),
+            Vector3::new(4., 1., 1.), Vector3::new(4., 2., 3.), Vector3::new(3., 3., 3.),
+        ];
+        let sweeps = [
+            Sweep::new(&SweepPath::Polyline(points.clone()), &radii, 12, true),
+            Sweep::new(&SweepPath::CatmullRom(points, 8), &radii, 12, true),
+            Sweep::new(&SweepPath::Bezier(bezier, 8), &[0.2, 0.1, 0.3], 12, true),
+        ];
+        for sweep in &sweeps {
+            let report = validate_mesh(sweep);
+            assert!(report.is_watertight(), "{}", report);
+        }
+        // without the caps both ends are open
+        let open = Sweep::new(&SweepPath::Polyline(vec![Vector3::new(0., 0., 0.), Vector3::new(0., 1., 0.)]), &[0.1, 0.1], 12, false);
+        assert_eq!(validate_mesh(&open).boundary_edges.len(), 2*12);
+    }
+
+    #[test]
+    fn planar_paths_do_not_twist() {
+        // a zigzag in the xz plane, the frames start with their normal in the plane
+        let points = vec![
+            Vector3::new(0., 0., 0.), Vector3::new(1., 0., 0.), Vector3::new(2., 0., 1.), Vector3::new(2., 0., 2.),
+            Vector3::new(1., 0., 3.), Vector3::new(1.5, 0., 4.), Vector3::new(3., 0., 4.),
+        ];
+        let sweep = Sweep::new(&SweepPath::Polyline(points.clone()), &[0.2; 7], 16, false);
+        for offset in get_ring_offsets(&sweep, &points, 16) {
+            assert!(offset.y.abs() < 1e-5, "the frame left the plane: {:?}", offset);
+            assert!((offset.magnitude() - 0.2).abs() < 1e-5);
+        }
+
+        // along a straight line all the frames are the same
+        let points: Vec<Vector3<f32>> = (0..10).map(|k| Vector3::new(1., 2., 3.)*(k as f32)).collect();
+        let sweep = Sweep::new(&SweepPath::Polyline(points.clone()), &[0.2; 10], 16, false);
+        let offsets = get_ring_offsets(&sweep, &points, 16);
+        for offset in &offsets {
+            assert!((offset - offsets[0]).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn rings_follow_the_interpolated_radii() {
+        let bezier = SweepPath::Bezier(vec![
+            Vector3::new(0., 0., 0.), Vector3::new(1., 1., 0.), Vector3::new(2., 1., 0.), Vector3::new(3., 0., 0.),
+            Vector3::new(4., -1., 0.), Vector3::new(5., 0., 1.), Vector3::new(6., 0., 2.),
+        ], 4);
+        let radii = [0.1, 0.3, 0.2];
+        let (points, sample_radii) = bezier.sample(&radii);
+        // 4 samples per segment, then the last anchor
+        let expected = [0.1, 0.15, 0.2, 0.25, 0.3, 0.275, 0.25, 0.225, 0.2];
+        assert_eq!(sample_radii.len(), expected.len());
+        for (radius, expected) in sample_radii.iter().zip(expected) {
+            assert!((radius - expected).abs() < 1e-6, "{:?}", sample_radii);
+        }
+
+        let sweep = Sweep::new(&bezier, &radii, 8, false);
+        assert_eq!(sweep.get_vertices().len(), points.len()*9);
+        for (k, &point) in points.iter().enumerate() {
+            for vertex in &sweep.get_vertices()[k*9..(k + 1)*9] {
+                let distance = (Vector3::from(vertex.get_position()) - point).magnitude();
+                assert!((distance - sample_radii[k]).abs() < 1e-5, "ring {}: {} instead of {}", k, distance, sample_radii[k]);
+            }
+        }
+    }
+}
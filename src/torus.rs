@@ -0,0 +1,43 @@
+use std::f32::consts::PI;
+use std::ops::Deref;
+
+use crate::cylinder::{Cylinder, Ring};
+use crate::mesh::TriangleMesh;
+
+// Ring shaped surface around the y axis: a circle of minor_radius swept at major_radius from the axis
+pub struct Torus(TriangleMesh);
+
+impl Deref for Torus {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl Torus {
+    // nb_slices around the y axis, nb_sides around the tube
+    pub fn new(nb_slices: usize, nb_sides: usize, major_radius: f32, minor_radius: f32) -> Self {
+        assert!(0. < minor_radius && minor_radius < major_radius, "the minor radius must be between 0 and the major radius");
+
+        let mut vertices = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        // rings around the tube, going from the outer equator under the bottom, the inner equator, over the top and back,
+        // the angle decreasing from 2π to 0 so the normals face outward.
+        // The last ring is a copy of the first one with v = 0 instead of 1
+        let rings: Vec<Ring> = (0..=nb_sides).map(|k| {
+            let v = 1. - (k as f32)/(nb_sides as f32);
+            let angle = 2.*PI*v;
+            Ring {
+                radius: major_radius + minor_radius*angle.cos(),
+                h: minor_radius*angle.sin(),
+                normal: [angle.cos(), angle.sin()],
+                v,
+            }
+        }).collect();
+        Cylinder::push_rings(&mut vertices, &mut indices, &rings, nb_slices, 0., 2.*PI, false);
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+}
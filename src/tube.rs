@@ -0,0 +1,95 @@
+use std::f32::consts::PI;
+use std::ops::Deref;
+
+use crate::cylinder::Cylinder;
+use crate::mesh::{triangle, TriangleMesh, Vertex};
+
+// Hollow cylinder (pipe) with a wall thickness: outer & inner walls joined by annulus caps
+pub struct Tube(TriangleMesh);
+
+impl Deref for Tube {
+    type Target = TriangleMesh;
+
+    fn deref(&self) -> &TriangleMesh {
+        &self.0
+    }
+}
+
+impl Tube {
+    pub fn new(nb_slices: usize, height: f32, inner_radius: f32, outer_radius: f32) -> Self {
+        assert!(0. < inner_radius && inner_radius < outer_radius, "the inner radius must be between 0 and the outer radius");
+
+        let mut vertices = Vec::new();
+        let mut indices: Vec<[u32; 3]> = Vec::new();
+
+        let (cosines, sines) = Cylinder::get_unit_circle_vertices(nb_slices, 0., 2.*PI);
+        let h = height/2.;
+
+        // walls: a top ring followed by a bottom ring, each with a duplicated seam vertex like the cylinder side wall.
+        // The inner wall faces the axis, seen from inside the angle increases from left to right
+        // so u follows it there while it goes the other way on the outer wall
+        for outer in [true, false] {
+            let (radius, sign) = if outer {(outer_radius, 1.)} else {(inner_radius, -1.)};
+            let top_start = vertices.len();
+            let bottom_start = top_start + nb_slices + 1;
+
+            for (y, v) in [(h, 1.), (-h, 0.)] {
+                for j in 0..=nb_slices {
+                    let u = (j as f32)/(nb_slices as f32);
+                    vertices.push(Vertex::new(
+                        [cosines[j]*radius, y, sines[j]*radius],
+                        [sign*cosines[j], 0., sign*sines[j]],
+                        [if outer {1. - u} else {u}, v],
+                        [sign*sines[j], 0., -sign*cosines[j], 1.],
+                    ));
+                }
+            }
+
+            for j in 0..nb_slices {
+                let (a, b) = (top_start + j, bottom_start + j);
+                if outer {
+                    indices.push(triangle(a, a+1, b));
+                    indices.push(triangle(a+1, b+1, b));
+                } else {
+                    indices.push(triangle(a, b, a+1));
+                    indices.push(triangle(a+1, b, b+1));
+                }
+            }
+        }
+
+        // annulus caps: an outer ring followed by an inner ring, the last slice wraps around to the first vertices.
+        // Same planar disc mapping as the cylinder caps, flipped on the bottom cap
+        for top in [true, false] {
+            let (y, sign) = if top {(h, 1.)} else {(-h, -1.)};
+            let outer_start = vertices.len();
+            let inner_start = outer_start + nb_slices;
+
+            for radius in [outer_radius, inner_radius] {
+                let scale = 0.5*radius/outer_radius;
+                for j in 0..nb_slices {
+                    vertices.push(Vertex::new(
+                        [cosines[j]*radius, y, sines[j]*radius],
+                        [0., sign, 0.],
+                        [0.5 + scale*cosines[j], 0.5 - sign*scale*sines[j]],
+                        [1., 0., 0., 1.],
+                    ));
+                }
+            }
+
+            for j in 0..nb_slices {
+                let next = (j+1)%nb_slices;
+                let (o, o_next) = (outer_start + j, outer_start + next);
+                let (i, i_next) = (inner_start + j, inner_start + next);
+                if top {
+                    indices.push(triangle(o_next, o, i));
+                    indices.push(triangle(o_next, i, i_next));
+                } else {
+                    indices.push(triangle(o, o_next, i));
+                    indices.push(triangle(i, o_next, i_next));
+                }
+            }
+        }
+
+        Self(TriangleMesh::new(vertices, indices))
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::bounds::Aabb;
+use crate::mesh::{Mesh, Vertex};
+use crate::optimize::get_weld_remap;
+
+// Problems found in a vertex/index set. Triangles are given by their position in the index list
+// and edges by a pair of vertex indices
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    // triangles referencing a vertex that doesn't exist
+    pub out_of_range: Vec<usize>,
+    // triangles with a null area or twice the same vertex
+    pub degenerate: Vec<usize>,
+    // edges shared by more than two triangles
+    pub non_manifold_edges: Vec<[usize; 2]>,
+    // edges used by a single triangle, i.e. holes in the surface
+    pub boundary_edges: Vec<[usize; 2]>,
+    // edges whose two triangles go through them in the same direction, i.e. one of them is flipped
+    pub inconsistent_winding: Vec<[usize; 2]>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.out_of_range.is_empty() && self.degenerate.is_empty()
+            && self.non_manifold_edges.is_empty() && self.inconsistent_winding.is_empty()
+    }
+
+    // valid and without any hole, i.e. the surface encloses a volume
+    pub fn is_watertight(&self) -> bool {
+        self.is_valid() && self.boundary_edges.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} out of range triangles, {} degenerate triangles, {} non manifold edges, {} boundary edges, {} inconsistently wound edges",
+            self.out_of_range.len(), self.degenerate.len(), self.non_manifold_edges.len(),
+            self.boundary_edges.len(), self.inconsistent_winding.len(),
+        )
+    }
+}
+
+pub fn validate_mesh(mesh: &dyn Mesh) -> ValidationReport {
+    validate(mesh.get_vertices(), mesh.get_indices())
+}
+
+// The topology is checked on positions rather than on vertex indices: the generators split vertices along
+// uv seams and hard edges (e.g. the cylinder cap rims), so vertices closer than a small fraction of the
+// mesh size are considered as the same point
+pub fn validate(vertices: &[Vertex], indices: &[[u32; 3]]) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let welded = weld_positions(vertices);
+
+    // directed edges (from, to) of the welded vertices, with the triangle they come from
+    let mut edges: HashMap<(usize, usize), Vec<(bool, usize)>> = HashMap::new();
+    for (triangle_index, triangle) in indices.iter().enumerate() {
+        if triangle.iter().any(|&index| index as usize >= vertices.len()) {
+            report.out_of_range.push(triangle_index);
+            continue;
+        }
+        let corners = triangle.map(|index| index as usize);
+        let [a, b, c] = corners.map(|index| Vector3::from(vertices[index].get_position()));
+        let [wa, wb, wc] = corners.map(|index| welded[index]);
+        if wa == wb || wb == wc || wc == wa || (b - a).cross(c - a).magnitude2() == 0. {
+            report.degenerate.push(triangle_index);
+            continue;
+        }
+
+        for (from, to) in [(wa, wb), (wb, wc), (wc, wa)] {
+            edges.entry((from.min(to), from.max(to))).or_default().push((from < to, triangle_index));
+        }
+    }
+
+    let mut sorted_edges: Vec<_> = edges.into_iter().collect();
+    sorted_edges.sort_by_key(|(edge, _)| *edge);
+    for ((from, to), uses) in sorted_edges {
+        match uses.len() {
+            1 => report.boundary_edges.push([from, to]),
+            2 => {
+                if uses[0].0 == uses[1].0 {
+                    report.inconsistent_winding.push([from, to]);
+                }
+            },
+            _ => report.non_manifold_edges.push([from, to]),
+        }
+    }
+    report
+}
+
+// index of the first vertex at the same position (within a small fraction of the mesh size) for every vertex
+fn weld_positions(vertices: &[Vertex]) -> Vec<usize> {
+    let bounds = Aabb::from_vertices(vertices);
+    let epsilon = if bounds.is_empty() {0.} else {(bounds.max - bounds.min).magnitude()*1e-5};
+    let (_, remap) = get_weld_remap(vertices, epsilon, |_, _| true);
+
+    // the welded vertices are numbered in the order they first appear
+    let mut first_vertices = Vec::new();
+    remap.iter().enumerate().map(|(index, &welded)| {
+        if welded as usize == first_vertices.len() {
+            first_vertices.push(index);
+        }
+        first_vertices[welded as usize]
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
+
+    use super::*;
+    use crate::capsule::Capsule;
+    use crate::cylinder::Cylinder;
+    use crate::tube::Tube;
+
+    fn vertex(position: [f32; 3]) -> Vertex {
+        Vertex::new(position, [0., 1., 0.], [0., 0.], [1., 0., 0., 1.])
+    }
+
+    // a tetrahedron wound outward
+    fn tetrahedron() -> (Vec<Vertex>, Vec<[u32; 3]>) {
+        let vertices = [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]].map(vertex).to_vec();
+        (vertices, vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]])
+    }
+
+    #[test]
+    fn generated_shapes_are_watertight() {
+        let cylinders = [
+            Cylinder::new(10, 1., 0.5),
+            Cylinder::builder(12, 2., 0.5).stacks(4).build(),
+            Cylinder::builder(12, 1., 0.5).sweep(Deg(30.), Deg(270.)).build(),
+            Cylinder::builder(12, 1., 0.5).stacks(3).sweep(Deg(0.), Deg(90.)).radii(0., 0.5).build(),
+        ];
+        for cylinder in &cylinders {
+            let report = validate_mesh(cylinder);
+            assert!(report.is_watertight(), "{}", report);
+        }
+        assert!(validate_mesh(&Tube::new(16, 1., 0.3, 0.5)).is_watertight());
+        assert!(validate_mesh(&Capsule::new(16, 8, 1., 0.5)).is_watertight());
+    }
+
+    #[test]
+    fn tetrahedron_is_watertight() {
+        let (vertices, indices) = tetrahedron();
+        assert!(validate(&vertices, &indices).is_watertight());
+    }
+
+    #[test]
+    fn out_of_range_triangle() {
+        let (vertices, mut indices) = tetrahedron();
+        indices.push([0, 1, 4]);
+        let report = validate(&vertices, &indices);
+        assert_eq!(report.out_of_range, vec![4]);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn degenerate_triangles() {
+        let (mut vertices, mut indices) = tetrahedron();
+        // twice the same vertex, then three aligned vertices
+        vertices.push(vertex([0.5, 0., 0.]));
+        indices.extend([[0, 1, 1], [0, 4, 1]]);
+        let report = validate(&vertices, &indices);
+        assert_eq!(report.degenerate, vec![4, 5]);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn flipped_triangle() {
+        let (vertices, mut indices) = tetrahedron();
+        indices[3] = [1, 3, 2];
+        let report = validate(&vertices, &indices);
+        assert_eq!(report.inconsistent_winding, vec![[1, 2], [1, 3], [2, 3]]);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn non_manifold_fin() {
+        let (mut vertices, mut indices) = tetrahedron();
+        vertices.push(vertex([1., 1., -1.]));
+        indices.push([0, 1, 4]);
+        let report = validate(&vertices, &indices);
+        assert_eq!(report.non_manifold_edges, vec![[0, 1]]);
+        assert_eq!(report.boundary_edges, vec![[0, 4], [1, 4]]);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn open_boundary() {
+        let (vertices, mut indices) = tetrahedron();
+        indices.pop();
+        let report = validate(&vertices, &indices);
+        assert_eq!(report.boundary_edges, vec![[1, 2], [1, 3], [2, 3]]);
+        assert!(report.is_valid() && !report.is_watertight());
+    }
+
+    #[test]
+    fn split_vertices_are_welded() {
+        // the same tetrahedron with a vertex per corner of each triangle
+        let (vertices, indices) = tetrahedron();
+        let split: Vec<Vertex> = indices.iter().flatten().map(|&index| vertices[index as usize]).collect();
+        let split_indices: Vec<[u32; 3]> = (0..4).map(|i| [3*i, 3*i + 1, 3*i + 2]).collect();
+        assert!(validate(&split, &split_indices).is_watertight());
+    }
+
+    #[test]
+    fn far_vertices() {
+        let vertices = vec![vertex([1e13, 0., 0.]); 3];
+        let report = validate(&vertices, &[[0, 1, 2]]);
+        assert_eq!(report.degenerate, vec![0]);
+    }
+}